@@ -1,19 +1,149 @@
-use std::{env::args, fs::read_to_string, process::exit};
-use vm::{Error, Vm};
+use std::{
+    env::args,
+    error,
+    fs::{read_to_string, write, File},
+    io::BufReader,
+    process::exit,
+};
+use vm::{Error, Profiler, Vm};
+
+const USAGE: &str = "usage: rvm [--profile] <file>
+       rvm [--profile] --image <image>
+       rvm --save-image <image> <file>
+       rvm disasm <file>
+       rvm check <file>";
+
+enum Command {
+    Run { profile: bool, source: Source },
+    SaveImage { image: String, path: String },
+    Disassemble { path: String },
+    Check { path: String },
+}
+
+enum Source {
+    Bytecode(String),
+    Image(String),
+}
 
 fn main() {
-    let input = match read_to_string(args().nth(1).expect("command line argument")) {
-        Ok(input) => input,
-        Err(error) => {
-            eprintln!("{error}");
-            return;
+    let command = parse_arguments(args().skip(1).collect()).unwrap_or_else(|| {
+        eprintln!("{USAGE}");
+        exit(2)
+    });
+
+    if let Err(error) = run(command) {
+        eprintln!("{error}");
+
+        exit(error.downcast_ref().map(exit_code).unwrap_or(1))
+    }
+}
+
+fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::IllegalInstruction(_) | Error::IllegalPrimitive(_) => 6,
+        Error::Runtime { error, .. } => exit_code(error),
+        _ => 1,
+    }
+}
+
+fn run(command: Command) -> Result<(), Box<dyn error::Error>> {
+    match command {
+        Command::Run { profile, source } => {
+            let mut profiler = Profiler::new();
+            let mut builder = Vm::builder();
+
+            if profile {
+                builder = builder.tracer(&mut profiler);
+            }
+
+            let result = match &source {
+                Source::Bytecode(path) => {
+                    let input = read_to_string(path)?;
+                    builder
+                        .build(input.trim().as_bytes())
+                        .and_then(|mut vm| vm.run())
+                }
+                Source::Image(path) => builder
+                    .build_image(&mut BufReader::new(File::open(path)?))
+                    .and_then(|mut vm| vm.run()),
+            };
+
+            if profile {
+                eprint!("{profiler}");
+            }
+
+            Ok(result?)
         }
-    };
+        Command::SaveImage { image, path } => {
+            let input = read_to_string(path)?;
+            let mut vm = Vm::try_new(input.trim().as_bytes())?;
+            let mut bytes = vec![];
+
+            // Run a prelude of a program so that it is skipped on loading the image.
+            vm.run_until_io()?;
+            vm.save_image(&mut bytes)?;
 
-    if let Err(error) = Vm::new(input.trim().as_bytes()).run() {
-        exit(match error {
-            Error::IllegalInstruction | Error::IllegalPrimitive => 6,
-            _ => 1,
-        })
+            Ok(write(image, bytes)?)
+        }
+        Command::Disassemble { path } => {
+            let input = read_to_string(path)?;
+
+            print!("{}", Vm::try_new(input.trim().as_bytes())?.disassemble());
+
+            Ok(())
+        }
+        Command::Check { path } => {
+            let input = read_to_string(path)?;
+            let diagnostics = Vm::try_new(input.trim().as_bytes())?.validate();
+
+            for diagnostic in &diagnostics {
+                println!("{diagnostic}");
+            }
+
+            if !diagnostics.is_empty() {
+                exit(1)
+            }
+
+            Ok(())
+        }
     }
 }
+
+fn parse_arguments(arguments: Vec<String>) -> Option<Command> {
+    let mut arguments = arguments.into_iter();
+    let mut profile = false;
+
+    loop {
+        match arguments.next()?.as_str() {
+            "disasm" if !profile => {
+                return single(arguments).map(|path| Command::Disassemble { path })
+            }
+            "check" if !profile => return single(arguments).map(|path| Command::Check { path }),
+            "--save-image" if !profile => {
+                let image = arguments.next()?;
+
+                return single(arguments).map(|path| Command::SaveImage { image, path });
+            }
+            "--image" => {
+                return single(arguments).map(|path| Command::Run {
+                    profile,
+                    source: Source::Image(path),
+                })
+            }
+            "--profile" => profile = true,
+            argument if argument.starts_with("--") => return None,
+            path => {
+                return arguments.next().is_none().then(|| Command::Run {
+                    profile,
+                    source: Source::Bytecode(path.into()),
+                })
+            }
+        }
+    }
+}
+
+fn single(mut arguments: impl Iterator<Item = String>) -> Option<String> {
+    let argument = arguments.next()?;
+
+    arguments.next().is_none().then_some(argument)
+}
@@ -0,0 +1,87 @@
+use crate::{error::Error, tracer::Tracer, vm::Vm};
+use std::io::{stdin, stdout, Read, Write};
+
+const DEFAULT_MAX_OBJECT_COUNT: usize = 1 << 14;
+
+pub struct VmBuilder<'a> {
+    pub(crate) max_objects: usize,
+    pub(crate) symbols: Vec<String>,
+    pub(crate) stack: Vec<i64>,
+    pub(crate) reader: Box<dyn Read + 'a>,
+    pub(crate) writer: Box<dyn Write + 'a>,
+    pub(crate) checked: bool,
+    pub(crate) tracer: Option<Box<dyn Tracer + 'a>>,
+}
+
+impl<'a> VmBuilder<'a> {
+    pub fn new() -> Self {
+        Self {
+            max_objects: DEFAULT_MAX_OBJECT_COUNT,
+            symbols: vec![],
+            stack: vec![],
+            reader: Box::new(stdin()),
+            writer: Box::new(stdout()),
+            checked: false,
+            tracer: None,
+        }
+    }
+
+    /// Sets a maximum number of ribs in each semi-space of a heap.
+    pub fn max_objects(mut self, count: usize) -> Self {
+        self.max_objects = count;
+        self
+    }
+
+    /// Adds symbols to a symbol table in addition to ones in bytecodes.
+    pub fn symbols(mut self, names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.symbols.extend(names.into_iter().map(Into::into));
+        self
+    }
+
+    /// Pushes numbers onto a stack of a program in order before it starts.
+    pub fn stack(mut self, numbers: impl IntoIterator<Item = i64>) -> Self {
+        self.stack.extend(numbers);
+        self
+    }
+
+    /// Sets an input stream for the `getc` primitive. It defaults to stdin.
+    pub fn reader(mut self, reader: impl Read + 'a) -> Self {
+        self.reader = Box::new(reader);
+        self
+    }
+
+    /// Sets an output stream for the `putc` primitive. It defaults to stdout.
+    pub fn writer(mut self, writer: impl Write + 'a) -> Self {
+        self.writer = Box::new(writer);
+        self
+    }
+
+    /// Enables type checking of operands for primitives.
+    pub fn checked(mut self, checked: bool) -> Self {
+        self.checked = checked;
+        self
+    }
+
+    /// Installs a tracer of events in a virtual machine. No events are traced by default.
+    pub fn tracer(mut self, tracer: impl Tracer + 'a) -> Self {
+        self.tracer = Some(Box::new(tracer));
+        self
+    }
+
+    pub fn build(self, input: &'a [u8]) -> Result<Vm<'a>, Error> {
+        Vm::with_configuration(input, self)
+    }
+
+    /// Builds a virtual machine from a heap image. A heap size and symbols are
+    /// restored from the image instead of the builder although the heap size is
+    /// limited by the maximum number of objects.
+    pub fn build_image(self, image: &mut impl Read) -> Result<Vm<'a>, Error> {
+        Vm::with_image(self, image)
+    }
+}
+
+impl Default for VmBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
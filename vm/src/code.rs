@@ -0,0 +1,44 @@
+/// A procedure in an instruction tree.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Procedure {
+    pub parameter_count: u64,
+    pub variadic: bool,
+    pub body: Vec<Code>,
+}
+
+impl Procedure {
+    pub fn new(parameter_count: u64, variadic: bool, body: Vec<Code>) -> Self {
+        Self {
+            parameter_count,
+            variadic,
+            body,
+        }
+    }
+}
+
+/// An instruction in an instruction tree.
+///
+/// Each instruction sequence ends with `Apply` as a jump or `If`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Code {
+    Apply(Operand),
+    Set(Operand),
+    Get(Operand),
+    Constant(Constant),
+    If(Vec<Code>, Vec<Code>),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Operand {
+    /// An index in a symbol table.
+    Symbol(u64),
+    /// An index in a stack.
+    Stack(u64),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Constant {
+    Number(u64),
+    Symbol(u64),
+    Procedure(Procedure),
+}
@@ -0,0 +1,54 @@
+use crate::name::display_name;
+use std::fmt::{self, Display, Formatter};
+
+/// A diagnostic reported by a validator.
+///
+/// A line is a 1-based line number in an output of a disassembler.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub kind: DiagnosticKind,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DiagnosticKind {
+    IllegalInstruction(u64),
+    IllegalPrimitive(u64),
+    NonProcedure(String),
+    NonProcedureOperand(u64),
+    StackOperandOutOfBounds(u64),
+    StackUnderflow,
+    UndefinedProcedure(String),
+    UnterminatedCode,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "line {}: ", self.line)?;
+
+        match &self.kind {
+            DiagnosticKind::IllegalInstruction(instruction) => {
+                write!(formatter, "illegal instruction {instruction}")
+            }
+            DiagnosticKind::IllegalPrimitive(primitive) => {
+                write!(formatter, "illegal primitive {primitive}")
+            }
+            DiagnosticKind::NonProcedure(name) => {
+                write!(formatter, "{} is not a procedure", display_name(name))
+            }
+            DiagnosticKind::NonProcedureOperand(index) => {
+                write!(formatter, "stack operand {index} is not a procedure")
+            }
+            DiagnosticKind::StackOperandOutOfBounds(index) => {
+                write!(formatter, "stack operand {index} out of bounds")
+            }
+            DiagnosticKind::StackUnderflow => write!(formatter, "stack underflow"),
+            DiagnosticKind::UndefinedProcedure(name) => {
+                write!(formatter, "undefined procedure {}", display_name(name))
+            }
+            DiagnosticKind::UnterminatedCode => {
+                write!(formatter, "code not terminated by a jump or halt")
+            }
+        }
+    }
+}
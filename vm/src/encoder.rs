@@ -0,0 +1,394 @@
+use crate::{
+    code::{Code, Constant, Operand, Procedure},
+    error::Error,
+    instruction::INSTRUCTION_WEIGHTS,
+};
+
+const JUMP: usize = 0;
+const CALL: usize = 1;
+const SET: usize = 2;
+const GET: usize = 3;
+const CONSTANT: usize = 4;
+const CLOSURE: usize = 5;
+
+const BUILTIN_SYMBOL_COUNT: usize = 4;
+const IF_CODE: u64 = 91;
+const INTEGER_BASE: u64 = 46;
+
+/// Encodes a program into Ribbit's textual bytecode format.
+///
+/// Symbols are indexed in the given order. Their names must consist of ASCII
+/// characters other than `,` and `;`. The first four symbols are bound to `rib`,
+/// `#f`, `#t` and `()` on decoding and must be present.
+pub fn encode(symbols: &[impl AsRef<str>], entry: &Procedure) -> Result<String, Error> {
+    let mut output = String::new();
+
+    encode_symbols(&mut output, symbols)?;
+    encode_procedure(&mut output, entry)?;
+
+    Ok(output)
+}
+
+/// Encodes a program of a procedure body with symbols following the built-in
+/// ones.
+#[cfg(test)]
+pub(crate) fn encode_program(symbols: &[&str], body: Vec<Code>) -> String {
+    encode(
+        &[["rib", "", "", ""].as_slice(), symbols].concat(),
+        &Procedure::new(0, false, body),
+    )
+    .unwrap()
+}
+
+fn encode_symbols(output: &mut String, symbols: &[impl AsRef<str>]) -> Result<(), Error> {
+    let names = symbols.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+
+    if let Some(name) = names.iter().find(|name| {
+        name.chars()
+            .any(|character| !character.is_ascii() || ",;".contains(character))
+    }) {
+        return Err(Error::IllegalSymbol(name.to_string()));
+    } else if names.len() < BUILTIN_SYMBOL_COUNT {
+        return Err(Error::MissingSymbols(names.len()));
+    }
+
+    // Trailing empty names are encoded as a count of non-printable symbols while at least
+    // one name is always decoded.
+    let count = names
+        .iter()
+        .rev()
+        .take_while(|name| name.is_empty())
+        .count()
+        .min(names.len().saturating_sub(1));
+
+    encode_integer(output, count as u64);
+
+    // Symbol names are encoded in a reversed order.
+    for (index, name) in names[..names.len() - count].iter().rev().enumerate() {
+        if index > 0 {
+            output.push(',');
+        }
+
+        output.extend(name.chars().rev());
+    }
+
+    output.push(';');
+
+    Ok(())
+}
+
+fn encode_procedure(output: &mut String, procedure: &Procedure) -> Result<(), Error> {
+    encode_codes(output, &procedure.body)?;
+
+    let parameter_info = procedure.parameter_count * 2 + procedure.variadic as u64;
+    let weight = INSTRUCTION_WEIGHTS[CLOSURE];
+
+    if parameter_info < weight {
+        encode_code(output, CLOSURE, parameter_info);
+    } else {
+        encode_code(output, CLOSURE, weight);
+        encode_integer(output, parameter_info);
+    }
+
+    Ok(())
+}
+
+// Instructions are encoded in a reversed order.
+fn encode_codes(output: &mut String, codes: &[Code]) -> Result<(), Error> {
+    let (last, codes) = codes.split_last().ok_or(Error::MalformedProcedure)?;
+
+    match last {
+        Code::Apply(operand) => encode_operand(output, JUMP, *operand),
+        Code::If(then, r#else) => {
+            encode_codes(output, r#else)?;
+            encode_codes(output, then)?;
+            write_code(output, IF_CODE);
+        }
+        _ => return Err(Error::MalformedProcedure),
+    }
+
+    for code in codes.iter().rev() {
+        match code {
+            Code::Apply(operand) => encode_operand(output, CALL, *operand),
+            Code::Set(operand) => encode_operand(output, SET, *operand),
+            Code::Get(operand) => encode_operand(output, GET, *operand),
+            Code::Constant(Constant::Number(number)) => encode_number(output, *number),
+            Code::Constant(Constant::Symbol(index)) => {
+                encode_operand(output, CONSTANT, Operand::Symbol(*index))
+            }
+            Code::Constant(Constant::Procedure(procedure)) => encode_procedure(output, procedure)?,
+            Code::If(_, _) => return Err(Error::MalformedProcedure),
+        }
+    }
+
+    Ok(())
+}
+
+fn encode_operand(output: &mut String, operation: usize, operand: Operand) {
+    let weight = INSTRUCTION_WEIGHTS[operation];
+
+    match operand {
+        // Short operands are symbols for jump, call and set instructions and numbers
+        // otherwise.
+        Operand::Symbol(index) if operation < GET && index < weight => {
+            encode_code(output, operation, index)
+        }
+        Operand::Stack(index) if operation >= GET && index < weight => {
+            encode_code(output, operation, index)
+        }
+        Operand::Symbol(index) => {
+            encode_code(output, operation, weight + 1);
+            encode_integer(output, index);
+        }
+        Operand::Stack(index) => {
+            encode_code(output, operation, weight);
+            encode_integer(output, index);
+        }
+    }
+}
+
+// Short and long operands of constant instructions are numbers unless they are
+// marked as symbols.
+fn encode_number(output: &mut String, number: u64) {
+    let weight = INSTRUCTION_WEIGHTS[CONSTANT];
+
+    if number < weight {
+        encode_code(output, CONSTANT, number);
+    } else {
+        encode_code(output, CONSTANT, weight);
+        encode_integer(output, number);
+    }
+}
+
+fn encode_code(output: &mut String, operation: usize, operand: u64) {
+    let offset = INSTRUCTION_WEIGHTS[..operation]
+        .iter()
+        .map(|weight| weight + 3)
+        .sum::<u64>();
+
+    write_code(output, offset + operand);
+}
+
+fn encode_integer(output: &mut String, mut integer: u64) {
+    let mut codes = vec![integer % INTEGER_BASE];
+    integer /= INTEGER_BASE;
+
+    while integer > 0 {
+        codes.push(INTEGER_BASE + integer % INTEGER_BASE);
+        integer /= INTEGER_BASE;
+    }
+
+    for code in codes.into_iter().rev() {
+        write_code(output, code);
+    }
+}
+
+fn write_code(output: &mut String, code: u64) {
+    // Ribbit avoids a backslash in its encoding.
+    output.push(if code == 57 {
+        '!'
+    } else {
+        char::from(code as u8 + 35)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::Vm;
+
+    const SYMBOLS: [&str; 4] = ["rib", "", "", ""];
+
+    fn decode(input: Result<String, Error>) -> Result<String, Error> {
+        let input = input?;
+
+        Vm::builder().build(input.as_bytes())?;
+
+        Ok(input)
+    }
+
+    #[test]
+    fn encode_symbols() {
+        assert_eq!(
+            decode(encode(
+                &["rib", "", "", "", "id"],
+                &Procedure::new(0, false, vec![Code::Apply(Operand::Symbol(4))])
+            )),
+            Ok("#di,,,,bir;'y".into())
+        );
+    }
+
+    #[test]
+    fn encode_empty_symbols() {
+        assert_eq!(
+            decode(encode(
+                &SYMBOLS,
+                &Procedure::new(0, false, vec![Code::Apply(Operand::Symbol(0))])
+            )),
+            Ok("&bir;#y".into())
+        );
+    }
+
+    #[test]
+    fn encode_variadic_procedure() {
+        assert_eq!(
+            decode(encode(
+                &SYMBOLS,
+                &Procedure::new(
+                    0,
+                    false,
+                    vec![
+                        Code::Constant(Constant::Procedure(Procedure::new(
+                            0,
+                            true,
+                            vec![
+                                Code::Get(Operand::Stack(0)),
+                                Code::Constant(Constant::Number(0)),
+                                Code::Constant(Constant::Number(0)),
+                                Code::Constant(Constant::Number(3)),
+                                Code::Apply(Operand::Symbol(0)),
+                            ]
+                        ))),
+                        Code::Constant(Constant::Number(1)),
+                        Code::Constant(Constant::Number(2)),
+                        Code::Constant(Constant::Number(3)),
+                        Code::Constant(Constant::Number(3)),
+                        Code::Apply(Operand::Stack(4)),
+                    ]
+                )
+            )),
+            // spell-checker: disable-next-line
+            Ok("&bir;7'nnml#nkk^zy".into())
+        );
+    }
+
+    #[test]
+    fn encode_large_operands() {
+        let input = encode(
+            &SYMBOLS,
+            &Procedure::new(
+                0,
+                false,
+                vec![
+                    Code::Constant(Constant::Number(1 << 40)),
+                    Code::Constant(Constant::Number(0)),
+                    Code::Constant(Constant::Number(0)),
+                    Code::Constant(Constant::Number(3)),
+                    Code::Apply(Operand::Symbol(0)),
+                ],
+            ),
+        )
+        .unwrap();
+        let mut vm = Vm::new(input.as_bytes());
+
+        vm.run().unwrap();
+    }
+
+    // ((rib 19 0 1) character)
+    fn write_character(character: u8) -> Vec<Code> {
+        vec![
+            Code::Constant(Constant::Number(19)),
+            Code::Constant(Constant::Number(0)),
+            Code::Constant(Constant::Number(1)),
+            Code::Constant(Constant::Number(3)),
+            Code::Apply(Operand::Symbol(0)),
+            Code::Constant(Constant::Number(character as u64)),
+            Code::Constant(Constant::Number(1)),
+            Code::Apply(Operand::Stack(2)),
+        ]
+    }
+
+    fn run(input: &str) -> Vec<u8> {
+        let mut output = vec![];
+
+        Vm::builder()
+            .writer(&mut output)
+            .build(input.as_bytes())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        output
+    }
+
+    #[test]
+    fn encode_call() {
+        let input = encode_program(&[], write_character(b'A'));
+
+        assert_eq!(run(&input), b"A");
+    }
+
+    #[test]
+    fn encode_constants() {
+        let input = encode(
+            &SYMBOLS,
+            &Procedure::new(
+                0,
+                false,
+                [write_character(b'\n'), write_character(b'A')].concat(),
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(run(&input), b"\nA");
+    }
+
+    #[test]
+    fn encode_if() {
+        let input = encode(
+            &SYMBOLS,
+            &Procedure::new(
+                0,
+                false,
+                vec![
+                    Code::Get(Operand::Symbol(1)),
+                    Code::If(write_character(b'T'), write_character(b'F')),
+                ],
+            ),
+        )
+        .unwrap();
+
+        assert_eq!(run(&input), b"F");
+    }
+
+    #[test]
+    fn fail_to_encode_empty_procedure() {
+        assert_eq!(
+            encode(&SYMBOLS, &Procedure::new(0, false, vec![])),
+            Err(Error::MalformedProcedure)
+        );
+    }
+
+    #[test]
+    fn fail_to_encode_unterminated_procedure() {
+        assert_eq!(
+            encode(
+                &SYMBOLS,
+                &Procedure::new(0, false, vec![Code::Get(Operand::Stack(0))])
+            ),
+            Err(Error::MalformedProcedure)
+        );
+    }
+
+    #[test]
+    fn fail_to_encode_missing_symbols() {
+        assert_eq!(
+            encode(
+                &[""; 0],
+                &Procedure::new(0, false, vec![Code::Apply(Operand::Symbol(0))])
+            ),
+            Err(Error::MissingSymbols(0))
+        );
+    }
+
+    #[test]
+    fn fail_to_encode_illegal_symbol() {
+        assert_eq!(
+            encode(
+                &["a,b"],
+                &Procedure::new(0, false, vec![Code::Apply(Operand::Symbol(0))])
+            ),
+            Err(Error::IllegalSymbol("a,b".into()))
+        );
+    }
+}
@@ -1,6 +1,263 @@
+use crate::{
+    frame::Frame,
+    name::display_name,
+    object::{Object, ObjectType},
+    primitive::Primitive,
+};
+use std::{
+    error,
+    fmt::{self, Display, Formatter},
+    io::{self, ErrorKind},
+    sync::Arc,
+};
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Error {
-    ArgumentCount,
-    IllegalInstruction,
-    IllegalPrimitive,
+    /// A procedure is called with a wrong number of arguments.
+    ///
+    /// A procedure name is present if the procedure is called by a symbol.
+    ArgumentCount {
+        procedure: Option<String>,
+        expected: usize,
+        variadic: bool,
+        actual: usize,
+    },
+    ArithmeticOverflow,
+    DivisionByZero,
+    /// An object is not convertible into or from a host value.
+    IllegalConversion(Object),
+    IllegalInstruction(u64),
+    /// A rib returned by a host primitive is not allocated in a heap.
+    IllegalObject(Object),
+    IllegalPrimitive(u64),
+    /// A non-procedure object is called.
+    IllegalProcedure(Object),
+    /// A symbol name is unknown or cannot be encoded.
+    IllegalSymbol(String),
+    IntegerOverflow(usize),
+    Io(IoError),
+    MalformedCode(usize),
+    MalformedImage,
+    MalformedProcedure,
+    /// A symbol table has fewer symbols than ones built into a virtual machine.
+    MissingSymbols(usize),
+    OutOfMemory,
+    /// An error during execution with a backtrace of the innermost frame first.
+    Runtime {
+        error: Box<Error>,
+        backtrace: Vec<Frame>,
+    },
+    TypeMismatch {
+        primitive: Primitive,
+        expected: ObjectType,
+    },
+    UnexpectedEndOfInput(usize),
+    UnsupportedImageVersion(u32),
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error.0.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error.into())
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::ArgumentCount {
+                procedure,
+                expected,
+                variadic,
+                actual,
+            } => {
+                write!(formatter, "wrong number of arguments")?;
+
+                if let Some(procedure) = procedure {
+                    write!(formatter, " to {}", display_name(procedure))?;
+                }
+
+                write!(
+                    formatter,
+                    ": expected {}{expected} but got {actual}",
+                    if *variadic { "at least " } else { "" }
+                )
+            }
+            Self::ArithmeticOverflow => write!(formatter, "arithmetic overflow"),
+            Self::DivisionByZero => write!(formatter, "division by zero"),
+            Self::IllegalConversion(object) => {
+                write!(formatter, "illegal conversion of {object:?}")
+            }
+            Self::IllegalInstruction(instruction) => {
+                write!(formatter, "illegal instruction {instruction}")
+            }
+            Self::IllegalObject(object) => write!(formatter, "illegal object {object:?}"),
+            Self::IllegalPrimitive(primitive) => write!(formatter, "illegal primitive {primitive}"),
+            Self::IllegalProcedure(object) => write!(formatter, "illegal procedure {object:?}"),
+            Self::IllegalSymbol(name) => write!(formatter, "illegal symbol {name}"),
+            Self::IntegerOverflow(position) => {
+                write!(formatter, "integer overflow at position {position}")
+            }
+            Self::Io(error) => write!(formatter, "{error}"),
+            Self::MalformedCode(position) => {
+                write!(formatter, "malformed code at position {position}")
+            }
+            Self::MalformedImage => write!(formatter, "malformed image"),
+            Self::MalformedProcedure => write!(formatter, "malformed procedure"),
+            Self::MissingSymbols(count) => {
+                write!(
+                    formatter,
+                    "missing symbols: expected at least 4 but got {count}"
+                )
+            }
+            Self::OutOfMemory => write!(formatter, "out of memory"),
+            Self::Runtime { error, backtrace } => {
+                write!(formatter, "{error}")?;
+
+                for frame in backtrace {
+                    write!(formatter, "\n  in {frame}")?;
+                }
+
+                Ok(())
+            }
+            Self::TypeMismatch {
+                primitive,
+                expected,
+            } => write!(
+                formatter,
+                "type mismatch in primitive {primitive:?}: expected {expected:?}"
+            ),
+            Self::UnexpectedEndOfInput(position) => {
+                write!(formatter, "unexpected end of input at position {position}")
+            }
+            Self::UnsupportedImageVersion(version) => {
+                write!(formatter, "unsupported image version {version}")
+            }
+        }
+    }
+}
+
+/// An I/O error shared between clones of an error.
+///
+/// I/O errors are equal if their kinds and messages are equal.
+#[derive(Clone, Debug)]
+pub struct IoError(Arc<io::Error>);
+
+impl IoError {
+    pub fn kind(&self) -> ErrorKind {
+        self.0.kind()
+    }
+}
+
+impl From<io::Error> for IoError {
+    fn from(error: io::Error) -> Self {
+        Self(error.into())
+    }
+}
+
+impl PartialEq for IoError {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind() == other.kind() && self.0.to_string() == other.0.to_string()
+    }
+}
+
+impl Eq for IoError {}
+
+impl Display for IoError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_argument_count() {
+        assert_eq!(
+            Error::ArgumentCount {
+                procedure: Some("f".into()),
+                expected: 2,
+                variadic: false,
+                actual: 1,
+            }
+            .to_string(),
+            "wrong number of arguments to f: expected 2 but got 1"
+        );
+        assert_eq!(
+            Error::ArgumentCount {
+                procedure: None,
+                expected: 1,
+                variadic: true,
+                actual: 0,
+            }
+            .to_string(),
+            "wrong number of arguments: expected at least 1 but got 0"
+        );
+    }
+
+    #[test]
+    fn display_illegal_objects() {
+        assert_eq!(
+            Error::IllegalConversion(Object::Rib(42)).to_string(),
+            "illegal conversion of Rib(42)"
+        );
+        assert_eq!(
+            Error::IllegalProcedure(Object::Number(0)).to_string(),
+            "illegal procedure Number(0)"
+        );
+        assert_eq!(
+            Error::IllegalSymbol("foo".into()).to_string(),
+            "illegal symbol foo"
+        );
+    }
+
+    #[test]
+    fn display_io_error() {
+        let error = Error::from(io::Error::other("disk on fire"));
+
+        assert_eq!(error.to_string(), "disk on fire");
+        assert_eq!(
+            error::Error::source(&error).map(ToString::to_string),
+            Some("disk on fire".into())
+        );
+    }
+
+    #[test]
+    fn display_runtime_error() {
+        assert_eq!(
+            Error::Runtime {
+                error: Error::DivisionByZero.into(),
+                backtrace: vec![
+                    Frame::default(),
+                    Frame {
+                        names: vec!["f".into(), "g".into()]
+                    }
+                ],
+            }
+            .to_string(),
+            "division by zero\n  in <local>\n  in f, g"
+        );
+    }
+
+    #[test]
+    fn display_type_mismatch() {
+        assert_eq!(
+            Error::TypeMismatch {
+                primitive: Primitive::Add,
+                expected: ObjectType::Number,
+            }
+            .to_string(),
+            "type mismatch in primitive Add: expected Number"
+        );
+    }
 }
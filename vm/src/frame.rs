@@ -0,0 +1,28 @@
+use crate::name::display_name;
+use std::fmt::{self, Display, Formatter};
+
+/// A call frame in a backtrace.
+///
+/// Names are of global variables bound to a procedure of the frame.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Frame {
+    pub names: Vec<String>,
+}
+
+impl Display for Frame {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        if self.names.is_empty() {
+            return write!(formatter, "<local>");
+        }
+
+        for (index, name) in self.names.iter().enumerate() {
+            if index > 0 {
+                write!(formatter, ", ")?;
+            }
+
+            write!(formatter, "{}", display_name(name))?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,6 +1,8 @@
 use num_derive::FromPrimitive;
 
-#[derive(Clone, Copy, Debug, FromPrimitive)]
+pub const INSTRUCTION_WEIGHTS: [u64; 6] = [20, 30, 0, 10, 11, 4];
+
+#[derive(Clone, Copy, Debug, Eq, FromPrimitive, Hash, PartialEq)]
 pub enum Instruction {
     Apply,
     Set,
@@ -1,9 +1,29 @@
+mod builder;
+mod code;
+mod diagnostic;
+mod encoder;
 mod error;
+mod frame;
 mod instruction;
+mod name;
 mod object;
 mod primitive;
+mod profiler;
 mod rib;
+mod status;
+mod tracer;
 mod vm;
 
-pub use error::Error;
-pub use vm::Vm;
+pub use builder::VmBuilder;
+pub use code::{Code, Constant, Operand, Procedure};
+pub use diagnostic::{Diagnostic, DiagnosticKind};
+pub use encoder::encode;
+pub use error::{Error, IoError};
+pub use frame::Frame;
+pub use instruction::Instruction;
+pub use object::{Object, ObjectType};
+pub use primitive::Primitive;
+pub use profiler::Profiler;
+pub use status::Status;
+pub use tracer::{StderrTracer, Tracer};
+pub use vm::{FromObject, IntoObject, Symbol, Vm};
@@ -0,0 +1,10 @@
+/// Returns a symbol name for display.
+///
+/// An empty name is displayed as `||` in the same way as Scheme.
+pub fn display_name(name: &str) -> &str {
+    if name.is_empty() {
+        "||"
+    } else {
+        name
+    }
+}
@@ -15,4 +15,17 @@ impl Object {
     pub const fn is_rib(&self) -> bool {
         matches!(self, Object::Rib(_))
     }
+
+    pub const fn r#type(&self) -> ObjectType {
+        match self {
+            Object::Number(_) => ObjectType::Number,
+            Object::Rib(_) => ObjectType::Rib,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, Debug, PartialEq)]
+pub enum ObjectType {
+    Number,
+    Rib,
 }
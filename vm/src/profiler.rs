@@ -0,0 +1,225 @@
+use crate::{
+    instruction::Instruction, name::display_name, object::Object, primitive::Primitive,
+    tracer::Tracer,
+};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+};
+
+const LOCAL_PROCEDURE_NAME: &str = "<local>";
+
+/// A profiler counting executed instructions, primitives, procedure entries and
+/// allocations.
+///
+/// It is installed into a virtual machine as a tracer and displays a report sorted
+/// by counts.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    instructions: HashMap<Instruction, u64>,
+    primitives: HashMap<Primitive, u64>,
+    host_primitives: HashMap<u64, u64>,
+    procedures: HashMap<String, u64>,
+    local_procedures: u64,
+    allocations: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a number of executions of an instruction.
+    pub fn instruction_count(&self, instruction: Instruction) -> u64 {
+        self.instructions
+            .get(&instruction)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns a number of calls of a primitive.
+    pub fn primitive_count(&self, primitive: Primitive) -> u64 {
+        self.primitives.get(&primitive).copied().unwrap_or_default()
+    }
+
+    /// Returns a number of calls of a primitive registered by a host.
+    pub fn host_primitive_count(&self, index: u64) -> u64 {
+        self.host_primitives
+            .get(&index)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns a number of entries into procedures fetched from a global symbol,
+    /// or from local variables if the name is `None`.
+    pub fn procedure_count(&self, name: Option<&str>) -> u64 {
+        if let Some(name) = name {
+            self.procedures.get(name).copied().unwrap_or_default()
+        } else {
+            self.local_procedures
+        }
+    }
+
+    /// Returns a number of allocated ribs.
+    pub fn allocation_count(&self) -> u64 {
+        self.allocations
+    }
+}
+
+impl Tracer for Profiler {
+    fn instruction(&mut self, instruction: Instruction, _operand: Object) {
+        *self.instructions.entry(instruction).or_default() += 1;
+    }
+
+    fn call(&mut self, symbol: Option<&str>) {
+        let Some(name) = symbol else {
+            self.local_procedures += 1;
+            return;
+        };
+
+        if let Some(count) = self.procedures.get_mut(name) {
+            *count += 1;
+        } else {
+            self.procedures.insert(name.into(), 1);
+        }
+    }
+
+    fn primitive(&mut self, primitive: Primitive) {
+        *self.primitives.entry(primitive).or_default() += 1;
+    }
+
+    fn host_primitive(&mut self, index: u64) {
+        *self.host_primitives.entry(index).or_default() += 1;
+    }
+
+    fn allocate(&mut self, _rib: Object) {
+        self.allocations += 1;
+    }
+}
+
+impl Display for Profiler {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write_section(
+            formatter,
+            "instructions",
+            self.instructions
+                .iter()
+                .map(|(instruction, count)| (format!("{instruction:?}"), *count)),
+        )?;
+        write_section(
+            formatter,
+            "primitives",
+            self.primitives
+                .iter()
+                .map(|(primitive, count)| (format!("{primitive:?}"), *count))
+                .chain(
+                    self.host_primitives
+                        .iter()
+                        .map(|(index, count)| (format!("Host({index})"), *count)),
+                ),
+        )?;
+        write_section(
+            formatter,
+            "procedures",
+            self.procedures
+                .iter()
+                .map(|(name, count)| (display_name(name).to_owned(), *count))
+                .chain(
+                    (self.local_procedures > 0)
+                        .then(|| (LOCAL_PROCEDURE_NAME.to_owned(), self.local_procedures)),
+                ),
+        )?;
+
+        writeln!(formatter, "allocations: {}", self.allocations)
+    }
+}
+
+fn write_section(
+    formatter: &mut Formatter,
+    title: &str,
+    counts: impl IntoIterator<Item = (String, u64)>,
+) -> fmt::Result {
+    let mut entries = counts.into_iter().collect::<Vec<_>>();
+
+    // Sort entries by counts in a descending order and then by names.
+    entries.sort_by(|(one, one_count), (other, other_count)| {
+        other_count.cmp(one_count).then_with(|| one.cmp(other))
+    });
+
+    writeln!(formatter, "{title}:")?;
+
+    for (name, count) in entries {
+        writeln!(formatter, "  {name}: {count}")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        code::{Code, Constant, Operand, Procedure},
+        encoder::encode_program,
+        vm::Vm,
+    };
+
+    #[test]
+    fn profile() {
+        // (define f (lambda () ((rib 1 0 1) 42))) (f)
+        let input = encode_program(
+            &["f"],
+            vec![
+                Code::Constant(Constant::Procedure(Procedure::new(
+                    0,
+                    false,
+                    vec![
+                        Code::Constant(Constant::Number(Primitive::Id as u64)),
+                        Code::Constant(Constant::Number(0)),
+                        Code::Constant(Constant::Number(1)),
+                        Code::Constant(Constant::Number(3)),
+                        Code::Apply(Operand::Symbol(0)),
+                        Code::Constant(Constant::Number(42)),
+                        Code::Constant(Constant::Number(1)),
+                        Code::Apply(Operand::Stack(2)),
+                    ],
+                ))),
+                Code::Set(Operand::Symbol(4)),
+                Code::Constant(Constant::Number(0)),
+                Code::Apply(Operand::Symbol(4)),
+            ],
+        );
+        let mut profiler = Profiler::new();
+
+        Vm::builder()
+            .tracer(&mut profiler)
+            .build(input.as_bytes())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(profiler.instruction_count(Instruction::Constant), 8);
+        assert_eq!(profiler.instruction_count(Instruction::Apply), 3);
+        assert_eq!(profiler.primitive_count(Primitive::Rib), 1);
+        assert_eq!(profiler.primitive_count(Primitive::Id), 1);
+        assert_eq!(profiler.procedure_count(Some("f")), 1);
+        assert_eq!(profiler.procedure_count(None), 0);
+        assert!(profiler.allocation_count() > 0);
+        assert!(profiler
+            .to_string()
+            .starts_with("instructions:\n  Constant: 8\n  Apply: 3\n"));
+    }
+
+    #[test]
+    fn profile_host_primitives() {
+        let mut profiler = Profiler::new();
+
+        profiler.host_primitive(100);
+        profiler.host_primitive(100);
+
+        assert_eq!(profiler.host_primitive_count(100), 2);
+        assert!(profiler
+            .to_string()
+            .contains("primitives:\n  Host(100): 2\n"));
+    }
+}
@@ -0,0 +1,8 @@
+/// A status of a virtual machine after a step of execution.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Status {
+    Continue,
+    Halted,
+    /// Execution is suspended as it runs out of fuel. It can be resumed later.
+    OutOfFuel,
+}
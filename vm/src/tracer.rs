@@ -0,0 +1,92 @@
+use crate::{instruction::Instruction, object::Object, primitive::Primitive};
+
+/// A tracer of events in a virtual machine.
+///
+/// All methods do nothing by default.
+pub trait Tracer {
+    /// Traces an instruction decoded at a position in bytecodes.
+    fn decode(&mut self, _position: usize, _instruction: Instruction, _operand: Object) {}
+
+    /// Traces an instruction before its execution.
+    fn instruction(&mut self, _instruction: Instruction, _operand: Object) {}
+
+    /// Traces an entry into a non-primitive procedure with a name of a global
+    /// symbol which the procedure is fetched from.
+    fn call(&mut self, _symbol: Option<&str>) {}
+
+    /// Traces a primitive before its execution.
+    fn primitive(&mut self, _primitive: Primitive) {}
+
+    /// Traces a primitive registered by a host before its execution.
+    fn host_primitive(&mut self, _index: u64) {}
+
+    /// Traces an allocation of a rib.
+    fn allocate(&mut self, _rib: Object) {}
+
+    /// Traces garbage collection with a number of live ribs after it.
+    fn collect_garbages(&mut self, _live_objects: usize) {}
+}
+
+impl<T: Tracer + ?Sized> Tracer for &mut T {
+    fn decode(&mut self, position: usize, instruction: Instruction, operand: Object) {
+        (**self).decode(position, instruction, operand)
+    }
+
+    fn instruction(&mut self, instruction: Instruction, operand: Object) {
+        (**self).instruction(instruction, operand)
+    }
+
+    fn call(&mut self, symbol: Option<&str>) {
+        (**self).call(symbol)
+    }
+
+    fn primitive(&mut self, primitive: Primitive) {
+        (**self).primitive(primitive)
+    }
+
+    fn host_primitive(&mut self, index: u64) {
+        (**self).host_primitive(index)
+    }
+
+    fn allocate(&mut self, rib: Object) {
+        (**self).allocate(rib)
+    }
+
+    fn collect_garbages(&mut self, live_objects: usize) {
+        (**self).collect_garbages(live_objects)
+    }
+}
+
+/// A tracer that writes events to stderr.
+#[derive(Debug, Default)]
+pub struct StderrTracer;
+
+impl Tracer for StderrTracer {
+    fn decode(&mut self, position: usize, instruction: Instruction, operand: Object) {
+        eprintln!("decode: {position} {instruction:?} {operand:?}");
+    }
+
+    fn instruction(&mut self, instruction: Instruction, operand: Object) {
+        eprintln!("instruction: {instruction:?} {operand:?}");
+    }
+
+    fn call(&mut self, symbol: Option<&str>) {
+        eprintln!("call: {}", symbol.unwrap_or("<local>"));
+    }
+
+    fn primitive(&mut self, primitive: Primitive) {
+        eprintln!("primitive: {primitive:?}");
+    }
+
+    fn host_primitive(&mut self, index: u64) {
+        eprintln!("host primitive: {index}");
+    }
+
+    fn allocate(&mut self, rib: Object) {
+        eprintln!("allocate: {rib:?}");
+    }
+
+    fn collect_garbages(&mut self, live_objects: usize) {
+        eprintln!("gc: {live_objects}");
+    }
+}
@@ -1,27 +1,36 @@
 use crate::{
+    builder::VmBuilder,
     error::Error,
-    instruction::Instruction,
-    object::Object,
+    instruction::{Instruction, INSTRUCTION_WEIGHTS},
+    object::{Object, ObjectType},
     primitive::Primitive,
     rib::{self, Rib, RibMut},
+    status::Status,
+    tracer::Tracer,
 };
+use num_traits::FromPrimitive;
 use std::{
+    collections::HashMap,
     convert::TryInto,
-    io::{stdin, Read},
-    ops::{Add, Div, Mul, Sub},
+    io::{ErrorKind, Read, Write},
+    iter,
 };
 
-const MAX_OBJECT_COUNT: usize = 1 << 14;
-const SPACE_SIZE: usize = MAX_OBJECT_COUNT * rib::FIELD_COUNT;
-const HEAP_SIZE: usize = SPACE_SIZE * 2;
-const HEAP_BOTTOM: usize = 0;
-const HEAP_MIDDLE: usize = HEAP_SIZE / 2;
-#[allow(dead_code)]
-const HEAP_TOP: usize = HEAP_SIZE;
+mod backtrace;
+mod conversion;
+mod disassembler;
+mod image;
+mod validator;
+
+pub use self::conversion::{FromObject, IntoObject, Symbol};
 
-const INSTRUCTION_WEIGHTS: [u64; 6] = [20, 30, 0, 10, 11, 4];
+const HEAP_BOTTOM: usize = 0;
 
 const ZERO: Object = Object::Number(0);
+const EOF: Object = Object::Number(-1i64 as u64);
+
+#[cfg(test)]
+const REPL: &[u8] = include_bytes!("../../test/repl.txt").trim_ascii();
 
 const PAIR_TAG: Object = ZERO;
 const CLOSURE_TAG: Object = Object::Number(1);
@@ -29,76 +38,179 @@ const SYMBOL_TAG: Object = Object::Number(2);
 const STRING_TAG: Object = Object::Number(3);
 const SINGLETON_TAG: Object = Object::Number(5);
 
+type HostProcedure<'a> = dyn FnMut(&[Object]) -> Object + 'a;
+
+struct HostPrimitive<'a> {
+    arity: usize,
+    procedure: Box<HostProcedure<'a>>,
+}
+
 pub struct Vm<'a> {
     // Roots
     stack: Object,
     program_counter: Object,
     r#false: Object,
+    host_roots: Vec<Object>,
 
     position: usize,
     input: &'a [u8],
 
+    reader: Box<dyn Read + 'a>,
+    writer: Box<dyn Write + 'a>,
+    checked: bool,
+    tracer: Option<Box<dyn Tracer + 'a>>,
+    primitives: HashMap<u64, HostPrimitive<'a>>,
+
     heap: Vec<Object>,
     symbol_table: Object,
 
+    space_size: usize,
     allocation_index: usize,
     allocation_limit: usize,
-    #[allow(dead_code)]
     scan: usize,
 }
 
 impl<'a> Vm<'a> {
     pub fn new(input: &'a [u8]) -> Self {
-        let mut vm = Self {
+        Self::try_new(input).expect("valid bytecodes")
+    }
+
+    pub fn try_new(input: &'a [u8]) -> Result<Self, Error> {
+        Self::builder().build(input)
+    }
+
+    pub fn builder() -> VmBuilder<'a> {
+        VmBuilder::new()
+    }
+
+    /// Creates a virtual machine from a heap image saved by [`Vm::save_image`].
+    pub fn load_image(image: &mut impl Read) -> Result<Self, Error> {
+        Self::builder().build_image(image)
+    }
+
+    pub(crate) fn with_configuration(
+        input: &'a [u8],
+        builder: VmBuilder<'a>,
+    ) -> Result<Self, Error> {
+        let (mut vm, symbols, stack) = Self::from_builder(input, builder)?;
+
+        vm.initialize(&symbols, &stack)?;
+
+        Ok(vm)
+    }
+
+    pub(crate) fn with_image(builder: VmBuilder<'a>, image: &mut impl Read) -> Result<Self, Error> {
+        let (mut vm, _, _) = Self::from_builder(&[], builder)?;
+
+        vm.read_image(image)?;
+
+        Ok(vm)
+    }
+
+    fn from_builder(
+        input: &'a [u8],
+        builder: VmBuilder<'a>,
+    ) -> Result<(Self, Vec<String>, Vec<i64>), Error> {
+        let VmBuilder {
+            max_objects,
+            symbols,
+            stack,
+            reader,
+            writer,
+            checked,
+            tracer,
+        } = builder;
+        let space_size = max_objects
+            .checked_mul(rib::FIELD_COUNT)
+            .ok_or(Error::OutOfMemory)?;
+        let heap_size = space_size.checked_mul(2).ok_or(Error::OutOfMemory)?;
+        let mut heap = vec![];
+
+        heap.try_reserve_exact(heap_size)
+            .map_err(|_| Error::OutOfMemory)?;
+        heap.resize(heap_size, ZERO);
+
+        let vm = Self {
             stack: ZERO,
             program_counter: ZERO,
             r#false: ZERO,
+            host_roots: vec![],
 
             position: 0,
             input,
-            heap: vec![ZERO; HEAP_SIZE],
+
+            reader,
+            writer,
+            checked,
+            tracer,
+            primitives: Default::default(),
+            heap,
             symbol_table: ZERO,
 
+            space_size,
             allocation_index: HEAP_BOTTOM,
-            allocation_limit: HEAP_MIDDLE,
+            allocation_limit: HEAP_BOTTOM + space_size,
             scan: 0,
         };
 
-        vm.initialize();
-
-        vm
+        Ok((vm, symbols, stack))
     }
 
-    fn initialize(&mut self) {
-        let r#true = self.allocate_rib(ZERO, ZERO, SINGLETON_TAG);
-        let nil = self.allocate_rib(ZERO, ZERO, SINGLETON_TAG);
-        self.r#false = self.allocate_rib(r#true, nil, SINGLETON_TAG);
+    fn initialize(&mut self, symbols: &[String], stack: &[i64]) -> Result<(), Error> {
+        let r#true = self.allocate_rib(ZERO, ZERO, SINGLETON_TAG)?;
+        let nil = self.allocate_rib(ZERO, ZERO, SINGLETON_TAG)?;
+        self.r#false = self.allocate_rib(r#true, nil, SINGLETON_TAG)?;
+
+        // Extra symbols are placed at the end of a symbol table not to change indices of
+        // symbols in bytecodes.
+        for symbol in symbols {
+            let mut name = self.get_nil();
+
+            for character in symbol.chars().rev() {
+                name = self.allocate_rib(Object::Number(character as u64), name, PAIR_TAG)?;
+            }
+
+            self.initialize_symbol(name)?;
+        }
 
-        self.decode_symbols();
-        self.decode_codes();
+        self.decode_symbols()?;
+        self.decode_codes()?;
 
         // Primitive 0
-        let rib = self.allocate_rib(ZERO, self.symbol_table, CLOSURE_TAG);
+        let rib = self.allocate_rib(ZERO, self.symbol_table, CLOSURE_TAG)?;
 
         // The symbol initialization order is important as they are listed in a symbol
         // table in encoded bytecodes.
-        self.initialize_global(rib);
-        self.initialize_global(self.r#false);
-        self.initialize_global(self.get_true());
-        self.initialize_global(self.get_nil());
+        let mut list = self.symbol_table;
 
-        self.initialize_stack();
+        for object in [rib, self.r#false, self.get_true(), self.get_nil()] {
+            list = self.initialize_global(list, object)?;
+        }
+
+        self.initialize_stack()?;
+
+        for &number in stack {
+            self.push(Object::Number(number as u64), PAIR_TAG)?;
+        }
+
+        Ok(())
     }
 
-    fn initialize_global(&mut self, object: Object) {
-        // TODO Review this.
-        *self.get_car_mut(self.get_car(self.symbol_table)) = object;
-        self.symbol_table = self.get_cdr(self.symbol_table);
+    // A symbol table is left in place so that built-in globals are found by
+    // names later.
+    fn initialize_global(&mut self, list: Object, object: Object) -> Result<Object, Error> {
+        if !list.is_rib() {
+            return Err(Error::MalformedCode(self.position));
+        }
+
+        *self.get_car_mut(self.get_car(list)) = object;
+
+        Ok(self.get_cdr(list))
     }
 
-    fn initialize_stack(&mut self) {
-        self.push(ZERO, PAIR_TAG);
-        self.push(ZERO, PAIR_TAG);
+    fn initialize_stack(&mut self) -> Result<(), Error> {
+        self.push(ZERO, PAIR_TAG)?;
+        self.push(ZERO, PAIR_TAG)?;
 
         let instruction = self.get_cdr(self.stack);
 
@@ -109,117 +221,304 @@ impl<'a> Vm<'a> {
         // TODO Do we need these?
         *self.get_cdr_mut(instruction) = ZERO;
         *self.get_tag_mut(instruction) = PAIR_TAG;
+
+        Ok(())
     }
 
+    /// Runs a program until it halts.
+    ///
+    /// Errors raised in procedures are returned as `Error::Runtime` with
+    /// backtraces. Out-of-memory and I/O errors are returned as they are.
     pub fn run(&mut self) -> Result<(), Error> {
-        loop {
-            let instruction = self.get_car(self.program_counter);
-            #[cfg(feature = "trace")]
-            println!("instruction: {}", instruction.to_raw());
-
-            match instruction.to_raw() {
-                Instruction::HALT => return Ok(()),
-                Instruction::APPLY => {
-                    let jump = self.get_tag(self.program_counter) == ZERO;
-                    let procedure = self.get_procedure();
-                    let code = self.get_code();
-                    let mut argument_count = self.pop();
-
-                    if !code.is_rib() {
-                        self.operate_primitive(
-                            Primitive::try_from(code.to_raw())
-                                .map_err(|_| Error::IllegalPrimitive)?,
-                        );
-
-                        if jump {
-                            self.program_counter = self.get_continuation();
-                            *self.get_cdr_mut(self.stack) = self.get_car(self.program_counter);
-                        }
-
-                        self.advance_program_counter();
-                    } else {
-                        debug_assert!(!self.get_car(code).is_rib());
-                        debug_assert!(!argument_count.is_rib());
-
-                        let parameter_info = self.get_car(code).to_raw();
-                        let parameter_count = Object::Number(parameter_info >> 1);
-                        let variadic = parameter_info & 1 != 0;
-
-                        let mut stack = self.allocate_rib(ZERO, procedure, PAIR_TAG);
-                        *self.get_car_mut(self.program_counter) = code;
-
-                        if (!variadic && parameter_count != argument_count)
-                            || (variadic && parameter_count.to_raw() > argument_count.to_raw())
-                        {
-                            return Err(Error::ArgumentCount);
-                        }
-
-                        argument_count =
-                            Object::Number(argument_count.to_raw() - parameter_count.to_raw());
-
-                        if variadic {
-                            todo!("{}", argument_count.to_raw());
-                        }
-
-                        for _ in 0..parameter_count.to_raw() {
-                            let argument = self.pop();
-                            stack = self.allocate_rib(argument, stack, PAIR_TAG);
-                        }
-
-                        let c2 = self.get_list_tail(
-                            stack,
-                            Object::Number(parameter_count.to_raw() + if variadic { 1 } else { 0 }),
-                        );
-
-                        if jump {
-                            let continuation = self.get_continuation();
-                            *self.get_car_mut(c2) = self.get_car(continuation);
-                            *self.get_tag_mut(c2) = self.get_tag(continuation);
-                        } else {
-                            *self.get_car_mut(c2) = self.stack;
-                            *self.get_tag_mut(c2) = self.get_tag(self.program_counter);
-                        }
-
-                        self.stack = stack;
-
-                        let next_counter = self.get_car(self.program_counter);
-                        *self.get_car_mut(self.program_counter) = instruction;
-                        self.program_counter = self.get_tag(next_counter);
-                    }
-                }
-                Instruction::SET => {
-                    let x = self.pop();
+        while self.step_with_backtrace()? == Status::Continue {}
+
+        Ok(())
+    }
+
+    /// Registers a primitive defined by a host.
+    ///
+    /// The primitive is bound to a global variable of a given name, which is
+    /// interned if it does not exist. It receives its arguments in order and
+    /// returns a result. Ribs in the result must be allocated in a current heap.
+    /// Indices of built-in primitives are reserved.
+    pub fn register_primitive(
+        &mut self,
+        index: u64,
+        name: &str,
+        arity: usize,
+        procedure: impl FnMut(&[Object]) -> Object + 'a,
+    ) -> Result<(), Error> {
+        if Primitive::try_from(index).is_ok() {
+            return Err(Error::IllegalPrimitive(index));
+        }
+
+        let root = self.host_roots.len();
+
+        // Keep the symbol in roots as allocating a closure can move it.
+        let symbol = self.intern(name)?;
+        self.host_roots.push(symbol);
+
+        let closure = self.allocate_rib(Object::Number(index), ZERO, CLOSURE_TAG);
+        let symbol = self.host_roots[root];
+
+        self.host_roots.truncate(root);
+        *self.get_car_mut(symbol) = closure?;
+
+        self.primitives.insert(
+            index,
+            HostPrimitive {
+                arity,
+                procedure: Box::new(procedure),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Returns a value of a global variable.
+    pub fn global(&self, name: &str) -> Option<Object> {
+        self.find_symbol(name).map(|symbol| self.get_car(symbol))
+    }
+
+    /// Sets a value of a global variable.
+    pub fn set_global(&mut self, name: &str, value: Object) -> Result<(), Error> {
+        let symbol = self
+            .find_symbol(name)
+            .ok_or_else(|| Error::IllegalSymbol(name.into()))?;
+
+        *self.get_car_mut(symbol) = value;
+
+        Ok(())
+    }
+
+    /// Returns a symbol of a name creating it if it does not exist.
+    ///
+    /// A new symbol is linked in place into a symbol table shared with Scheme
+    /// code so that `string->symbol` returns the same symbol for the name.
+    pub fn intern(&mut self, name: &str) -> Result<Object, Error> {
+        if let Some(symbol) = self.find_symbol(name) {
+            return Ok(symbol);
+        }
 
-                    let rib = if !self.get_cdr(self.program_counter).is_rib() {
-                        self.get_list_tail(self.stack, self.get_cdr(self.program_counter))
+        let string = name.into_object(self)?;
+        let symbol = self.allocate_symbol(string)?;
+        // Link the symbol after the first entry as lists in Scheme end with the
+        // whole table.
+        let list = self.allocate_rib(symbol, self.get_cdr(self.symbol_table), PAIR_TAG)?;
+
+        *self.get_cdr_mut(self.symbol_table) = list;
+
+        Ok(self.get_car(list))
+    }
+
+    /// Calls a procedure with arguments and returns its result.
+    ///
+    /// A program counter and a stack are restored after the call even on errors so
+    /// that a host can call procedures repeatedly. Objects held by the host are
+    /// invalidated by the call as it can collect garbages.
+    pub fn call(&mut self, procedure: Object, arguments: &[Object]) -> Result<Object, Error> {
+        if !procedure.is_rib() || self.get_tag(procedure) != CLOSURE_TAG {
+            return Err(Error::IllegalProcedure(procedure));
+        }
+
+        let root = self.host_roots.len();
+
+        // Keep a program counter and a stack in roots to restore them after the call
+        // and the procedure and arguments as pushing them can move them.
+        self.host_roots
+            .extend([self.program_counter, self.stack, procedure]);
+        self.host_roots.extend(arguments);
+
+        let result = self.call_rooted(root + 2, arguments.len());
+
+        self.program_counter = self.host_roots[root];
+        self.stack = self.host_roots[root + 1];
+        self.host_roots.truncate(root);
+
+        result
+    }
+
+    fn call_rooted(&mut self, root: usize, argument_count: usize) -> Result<Object, Error> {
+        self.initialize_call(root, argument_count)?;
+
+        while self.step_with_backtrace()? == Status::Continue {}
+
+        Ok(self.pop())
+    }
+
+    fn initialize_call(&mut self, root: usize, argument_count: usize) -> Result<(), Error> {
+        for index in root..self.host_roots.len() {
+            self.push(self.host_roots[index], PAIR_TAG)?;
+        }
+
+        self.push(Object::Number(argument_count as u64), PAIR_TAG)?;
+
+        // The procedure returns to a halt instruction after the call.
+        let halt = self.allocate_rib(Object::Number(Instruction::HALT), ZERO, PAIR_TAG)?;
+        // Keep the continuation in a tag rooted as the allocation can move it.
+        self.program_counter = self.allocate_rib2(
+            Object::Number(Instruction::APPLY),
+            Object::Number(argument_count as u64 + 1),
+            halt,
+        )?;
+
+        Ok(())
+    }
+
+    /// Runs at most a given number of instructions.
+    ///
+    /// It returns `Status::OutOfFuel` if a program has not halted yet. Then, a
+    /// virtual machine keeps its state and a subsequent call resumes execution.
+    pub fn run_with_fuel(&mut self, fuel: usize) -> Result<Status, Error> {
+        for _ in 0..fuel {
+            if self.step_with_backtrace()? == Status::Halted {
+                return Ok(Status::Halted);
+            }
+        }
+
+        Ok(if self.instruction() == Some(Instruction::Halt) {
+            Status::Halted
+        } else {
+            Status::OutOfFuel
+        })
+    }
+
+    /// Runs a program until it halts or is about to read or write a character.
+    ///
+    /// It returns `Status::Continue` if the program is paused before the `getc` or
+    /// `putc` primitive. Then, a heap image saved at this point lets the program
+    /// skip its initialization on later runs.
+    pub fn run_until_io(&mut self) -> Result<Status, Error> {
+        while !self.is_io_pending() {
+            if self.step_with_backtrace()? == Status::Halted {
+                return Ok(Status::Halted);
+            }
+        }
+
+        Ok(Status::Continue)
+    }
+
+    fn is_io_pending(&self) -> bool {
+        if self.instruction() != Some(Instruction::Apply) {
+            return false;
+        }
+
+        let procedure = self.get_procedure();
+
+        procedure.is_rib()
+            && matches!(
+                Primitive::try_from(self.get_car(procedure).to_raw()),
+                Ok(Primitive::GetC | Primitive::PutC)
+            )
+    }
+
+    fn step_with_backtrace(&mut self) -> Result<Status, Error> {
+        self.step().map_err(|error| self.with_backtrace(error))
+    }
+
+    /// Executes an instruction at a program counter.
+    ///
+    /// It never returns `Status::OutOfFuel` as it does not consume fuel.
+    pub fn step(&mut self) -> Result<Status, Error> {
+        let instruction = self.get_car(self.program_counter);
+
+        if self.tracer.is_some() {
+            if let Some(instruction) = Instruction::from_u64(instruction.to_raw()) {
+                let operand = self.get_cdr(self.program_counter);
+                self.trace(|tracer| tracer.instruction(instruction, operand));
+            }
+        }
+
+        match instruction.to_raw() {
+            Instruction::HALT => return Ok(Status::Halted),
+            Instruction::APPLY => {
+                let jump = self.get_tag(self.program_counter) == ZERO;
+                let procedure = self.get_procedure();
+                let code = self.get_code();
+                let argument_count = self.pop();
+
+                if !code.is_rib() {
+                    if let Ok(primitive) = Primitive::try_from(code.to_raw()) {
+                        self.operate_primitive(primitive)?;
                     } else {
-                        self.get_cdr(self.program_counter)
-                    };
+                        self.operate_host_primitive(code.to_raw(), argument_count)?;
+                    }
 
-                    *self.get_car_mut(rib) = x;
+                    if jump {
+                        self.program_counter = self.get_continuation();
+                        *self.get_cdr_mut(self.stack) = self.get_car(self.program_counter);
+                    }
 
                     self.advance_program_counter();
+                } else if let Err(error) =
+                    self.apply_closure(instruction, procedure, argument_count, jump)
+                {
+                    // Restore the instruction as its car may hold code of the procedure.
+                    *self.get_car_mut(self.program_counter) = instruction;
+
+                    return Err(error);
                 }
-                Instruction::GET => {
-                    self.push(
-                        self.get_operand(self.get_cdr(self.program_counter)),
-                        PAIR_TAG,
-                    );
-                    self.advance_program_counter();
-                }
-                Instruction::CONSTANT => {
-                    self.push(self.get_cdr(self.program_counter), PAIR_TAG);
-                    self.advance_program_counter();
-                }
-                Instruction::IF => {
-                    self.program_counter = if self.pop().to_raw() != self.r#false.to_raw() {
-                        self.get_cdr(self.program_counter)
-                    } else {
-                        self.get_tag(self.program_counter)
-                    };
-                }
-                _ => return Err(Error::IllegalInstruction),
             }
+            Instruction::SET => {
+                let x = self.pop();
+
+                let rib = if !self.get_cdr(self.program_counter).is_rib() {
+                    self.get_list_tail(self.stack, self.get_cdr(self.program_counter))
+                } else {
+                    self.get_cdr(self.program_counter)
+                };
+
+                *self.get_car_mut(rib) = x;
+
+                self.advance_program_counter();
+            }
+            Instruction::GET => {
+                self.push(
+                    self.get_operand(self.get_cdr(self.program_counter)),
+                    PAIR_TAG,
+                )?;
+                self.advance_program_counter();
+            }
+            Instruction::CONSTANT => {
+                self.push(self.get_cdr(self.program_counter), PAIR_TAG)?;
+                self.advance_program_counter();
+            }
+            Instruction::IF => {
+                self.program_counter = if self.pop() != self.r#false {
+                    self.get_cdr(self.program_counter)
+                } else {
+                    self.get_tag(self.program_counter)
+                };
+            }
+            instruction => return Err(Error::IllegalInstruction(instruction)),
+        }
+
+        Ok(Status::Continue)
+    }
+
+    /// Returns an instruction at a program counter.
+    pub fn instruction(&self) -> Option<Instruction> {
+        Instruction::from_u64(self.get_car(self.program_counter).to_raw())
+    }
+
+    /// Returns objects in an operand stack of a current frame from its top.
+    pub fn stack(&self) -> impl Iterator<Item = Object> + '_ {
+        let mut stack = self.stack;
+
+        iter::from_fn(move || {
+            if !stack.is_rib() || self.get_tag(stack) != PAIR_TAG {
+                return None;
+            }
+
+            let object = self.get_car(stack);
+            stack = self.get_cdr(stack);
+            Some(object)
+        })
+    }
+
+    fn trace(&mut self, trace: impl FnOnce(&mut dyn Tracer)) {
+        if let Some(tracer) = &mut self.tracer {
+            trace(tracer.as_mut());
         }
     }
 
@@ -233,19 +532,35 @@ impl<'a> Vm<'a> {
         value
     }
 
-    fn push(&mut self, car: Object, tag: Object) {
+    fn push(&mut self, mut car: Object, mut tag: Object) -> Result<(), Error> {
+        if self.allocation_index == self.allocation_limit {
+            // Keep fields of a new rib in roots as they can be moved.
+            self.host_roots.extend([car, tag]);
+            self.collect_garbages();
+            tag = self.host_roots.pop().unwrap_or(tag);
+            car = self.host_roots.pop().unwrap_or(car);
+
+            // Live ribs fill up a whole semi-space and no rib can be allocated anymore.
+            if self.allocation_index == self.allocation_limit {
+                return Err(Error::OutOfMemory);
+            }
+        }
+
         self.heap[self.allocation_index..self.allocation_index + rib::FIELD_COUNT]
             .copy_from_slice(&[car, self.stack, tag]);
         self.stack = Object::Rib(self.allocation_index as u64);
         self.allocation_index += rib::FIELD_COUNT;
 
-        if self.allocation_index == self.allocation_limit {
-            self.collect_garbages();
+        if self.tracer.is_some() {
+            let rib = self.stack;
+            self.trace(|tracer| tracer.allocate(rib));
         }
+
+        Ok(())
     }
 
-    fn allocate_rib(&mut self, car: Object, cdr: Object, tag: Object) -> Object {
-        self.push(car, cdr);
+    fn allocate_rib(&mut self, car: Object, cdr: Object, tag: Object) -> Result<Object, Error> {
+        self.push(car, cdr)?;
         let stack = self.get_cdr(self.stack);
         let allocated = self.stack;
 
@@ -254,11 +569,11 @@ impl<'a> Vm<'a> {
 
         self.stack = stack;
 
-        allocated
+        Ok(allocated)
     }
 
-    fn allocate_rib2(&mut self, car: Object, cdr: Object, tag: Object) -> Object {
-        self.push(car, tag);
+    fn allocate_rib2(&mut self, car: Object, cdr: Object, tag: Object) -> Result<Object, Error> {
+        self.push(car, tag)?;
         let stack = self.get_cdr(self.stack);
         let allocated = self.stack;
 
@@ -266,7 +581,7 @@ impl<'a> Vm<'a> {
 
         self.stack = stack;
 
-        allocated
+        Ok(allocated)
     }
 
     fn get_rib(&self, index: Object) -> Rib<'_> {
@@ -332,7 +647,7 @@ impl<'a> Vm<'a> {
     fn get_list_length(&mut self, mut list: Object) -> Object {
         let mut len = 0;
 
-        while list.is_rib() && self.get_tag(list).to_raw() == 0 {
+        while list.is_rib() && self.get_tag(list) == PAIR_TAG {
             len += 1;
             list = self.get_cdr(list)
         }
@@ -348,9 +663,108 @@ impl<'a> Vm<'a> {
         }
     }
 
-    fn get_symbol_ref(&self, index: Object) -> Object {
-        self.get_rib(self.get_list_tail(self.symbol_table, index))
-            .car()
+    fn get_symbol_ref(&self, index: Object) -> Option<Object> {
+        let mut list = self.symbol_table;
+
+        for _ in 0..index.to_raw() {
+            if !list.is_rib() {
+                return None;
+            }
+
+            list = self.get_cdr(list);
+        }
+
+        list.is_rib().then(|| self.get_car(list))
+    }
+
+    // Unnamed symbols are never found by names.
+    fn find_symbol(&self, name: &str) -> Option<Object> {
+        if name.is_empty() {
+            return None;
+        }
+
+        let find = |table| {
+            self.symbols(table)
+                .find(|&symbol| self.get_symbol_name(symbol).as_deref() == Some(name))
+        };
+
+        find(self.symbol_table).or_else(|| {
+            let table = self.find_symbol_table();
+
+            (table != self.symbol_table).then(|| find(table)).flatten()
+        })
+    }
+
+    // Scheme code keeps its symbol table in a global variable and replaces it
+    // with a longer list whenever it creates a symbol. Then, the table is a list
+    // of symbols ending with the one known to a virtual machine.
+    fn find_symbol_table(&self) -> Object {
+        self.symbols(self.symbol_table)
+            .map(|symbol| self.get_car(symbol))
+            .find(|&list| {
+                list != self.symbol_table && self.is_symbol_list_tail(list, self.symbol_table)
+            })
+            .unwrap_or(self.symbol_table)
+    }
+
+    fn symbols(&self, mut list: Object) -> impl Iterator<Item = Object> + '_ {
+        iter::from_fn(move || {
+            while self.is_pair(list) {
+                let symbol = self.get_car(list);
+                list = self.get_cdr(list);
+
+                if self.is_symbol(symbol) {
+                    return Some(symbol);
+                }
+            }
+
+            None
+        })
+    }
+
+    fn is_symbol_list_tail(&self, mut list: Object, tail: Object) -> bool {
+        // Lists can be circular.
+        for _ in 0..self.space_size / rib::FIELD_COUNT {
+            if list == tail {
+                return true;
+            } else if !self.is_pair(list) || !self.is_symbol(self.get_car(list)) {
+                return false;
+            }
+
+            list = self.get_cdr(list);
+        }
+
+        false
+    }
+
+    fn is_pair(&self, object: Object) -> bool {
+        object.is_rib() && self.get_tag(object) == PAIR_TAG
+    }
+
+    fn is_symbol(&self, object: Object) -> bool {
+        object.is_rib() && self.get_tag(object) == SYMBOL_TAG
+    }
+
+    fn get_symbol_name(&self, symbol: Object) -> Option<String> {
+        if !symbol.is_rib() || self.get_tag(symbol) != SYMBOL_TAG {
+            return None;
+        }
+
+        let string = self.get_cdr(symbol);
+
+        if !string.is_rib() || self.get_tag(string) != STRING_TAG {
+            return None;
+        }
+
+        let mut name = String::new();
+        let mut list = self.get_car(string);
+
+        while list.is_rib() && self.get_tag(list) == PAIR_TAG {
+            name.extend(char::from_u32(self.get_car(list).to_raw() as u32));
+            list = self.get_cdr(list);
+        }
+
+        Some(name)
     }
 
     fn get_operand(&self, object: Object) -> Object {
@@ -362,6 +776,87 @@ impl<'a> Vm<'a> {
         .car()
     }
 
+    fn apply_closure(
+        &mut self,
+        instruction: Object,
+        procedure: Object,
+        mut argument_count: Object,
+        jump: bool,
+    ) -> Result<(), Error> {
+        let code = self.get_car(procedure);
+
+        if self.tracer.is_some() {
+            let name = self.get_procedure_name();
+            self.trace(|tracer| tracer.call(name.as_deref()));
+        }
+
+        debug_assert!(!self.get_car(code).is_rib());
+        debug_assert!(!argument_count.is_rib());
+
+        let parameter_info = self.get_car(code).to_raw();
+        let parameter_count = Object::Number(parameter_info >> 1);
+        let variadic = parameter_info & 1 != 0;
+
+        let mut stack = self.allocate_rib(ZERO, procedure, PAIR_TAG)?;
+        *self.get_car_mut(self.program_counter) = self.get_car(self.get_cdr(stack));
+
+        if (!variadic && parameter_count != argument_count)
+            || (variadic && parameter_count.to_raw() > argument_count.to_raw())
+        {
+            return Err(Error::ArgumentCount {
+                procedure: self.get_procedure_name(),
+                expected: parameter_count.to_raw() as usize,
+                variadic,
+                actual: argument_count.to_raw() as usize,
+            });
+        }
+
+        argument_count = Object::Number(argument_count.to_raw() - parameter_count.to_raw());
+
+        if variadic {
+            // Keep the frame on the stack as it is not reachable from any
+            // roots during allocation of rest arguments.
+            self.push(stack, PAIR_TAG)?;
+            let mut rest = self.get_nil();
+
+            for _ in 0..argument_count.to_raw() {
+                let argument = self.get_cdr(self.stack);
+                *self.get_cdr_mut(self.stack) = self.get_cdr(argument);
+                rest = self.allocate_rib(self.get_car(argument), rest, PAIR_TAG)?;
+            }
+
+            stack = self.pop();
+            stack = self.allocate_rib(rest, stack, PAIR_TAG)?;
+        }
+
+        for _ in 0..parameter_count.to_raw() {
+            let argument = self.pop();
+            stack = self.allocate_rib(argument, stack, PAIR_TAG)?;
+        }
+
+        let c2 = self.get_list_tail(
+            stack,
+            Object::Number(parameter_count.to_raw() + if variadic { 1 } else { 0 }),
+        );
+
+        if jump {
+            let continuation = self.get_continuation();
+            *self.get_car_mut(c2) = self.get_car(continuation);
+            *self.get_tag_mut(c2) = self.get_tag(continuation);
+        } else {
+            *self.get_car_mut(c2) = self.stack;
+            *self.get_tag_mut(c2) = self.get_tag(self.program_counter);
+        }
+
+        self.stack = stack;
+
+        let next_counter = self.get_car(self.program_counter);
+        *self.get_car_mut(self.program_counter) = instruction;
+        self.program_counter = self.get_tag(next_counter);
+
+        Ok(())
+    }
+
     fn get_procedure(&self) -> Object {
         self.get_operand(self.get_cdr(self.program_counter))
     }
@@ -370,10 +865,15 @@ impl<'a> Vm<'a> {
         self.get_car(self.get_procedure())
     }
 
+    // Returns a name of a procedure called by a current instruction.
+    fn get_procedure_name(&self) -> Option<String> {
+        self.get_symbol_name(self.get_cdr(self.program_counter))
+    }
+
     fn get_continuation(&self) -> Object {
         let mut stack = self.stack;
 
-        while self.get_tag(stack).to_raw() == 0 {
+        while self.get_tag(stack) == PAIR_TAG {
             stack = self.get_cdr(stack);
         }
 
@@ -393,21 +893,24 @@ impl<'a> Vm<'a> {
         &mut self.heap[index]
     }
 
-    fn operate_primitive(&mut self, primitive: Primitive) {
-        #[cfg(feature = "trace")]
-        println!("primitive: {}", primitive as usize);
+    fn operate_primitive(&mut self, primitive: Primitive) -> Result<(), Error> {
+        self.trace(|tracer| tracer.primitive(primitive));
+
+        if self.checked {
+            self.check_operands(primitive)?;
+        }
 
         match primitive {
             Primitive::Rib => {
-                let rib = self.allocate_rib(ZERO, ZERO, ZERO);
-                *self.get_car_mut(rib) = self.pop();
-                *self.get_cdr_mut(rib) = self.pop();
+                let rib = self.allocate_rib(ZERO, ZERO, ZERO)?;
                 *self.get_tag_mut(rib) = self.pop();
-                self.push(rib, PAIR_TAG);
+                *self.get_cdr_mut(rib) = self.pop();
+                *self.get_car_mut(rib) = self.pop();
+                self.push(rib, PAIR_TAG)?;
             }
             Primitive::Id => {
                 let x = self.pop();
-                self.push(x, PAIR_TAG);
+                self.push(x, PAIR_TAG)?;
             }
             Primitive::Pop => {
                 self.pop();
@@ -415,161 +918,320 @@ impl<'a> Vm<'a> {
             Primitive::Skip => {
                 let x = self.pop();
                 self.pop();
-                self.push(x, PAIR_TAG);
+                self.push(x, PAIR_TAG)?;
             }
             Primitive::Close => {
                 // TODO Review this.
                 let x = self.get_car(self.get_tos());
                 let y = self.get_cdr(self.stack);
 
-                *self.get_tos_mut() = self.allocate_rib(x, y, CLOSURE_TAG);
+                *self.get_tos_mut() = self.allocate_rib(x, y, CLOSURE_TAG)?;
             }
             Primitive::IsRib => {
                 let x = self.pop();
-                self.push(self.get_boolean(x.is_rib()), PAIR_TAG);
+                self.push(self.get_boolean(x.is_rib()), PAIR_TAG)?;
             }
             Primitive::Field0 => {
                 let x = self.pop();
-                self.push(self.get_car(x), PAIR_TAG);
+                self.push(self.get_car(x), PAIR_TAG)?;
             }
             Primitive::Field1 => {
                 let x = self.pop();
-                self.push(self.get_cdr(x), PAIR_TAG);
+                self.push(self.get_cdr(x), PAIR_TAG)?;
             }
             Primitive::Field2 => {
                 let x = self.pop();
-                self.push(self.get_tag(x), PAIR_TAG)
+                self.push(self.get_tag(x), PAIR_TAG)?
             }
             Primitive::SetField0 => {
-                let x = self.pop();
                 let y = self.pop();
+                let x = self.pop();
                 *self.get_car_mut(x) = y;
-                self.push(y, PAIR_TAG);
+                self.push(y, PAIR_TAG)?;
             }
             Primitive::SetField1 => {
-                let x = self.pop();
                 let y = self.pop();
+                let x = self.pop();
                 *self.get_cdr_mut(x) = y;
-                self.push(y, PAIR_TAG);
+                self.push(y, PAIR_TAG)?;
             }
             Primitive::SetField2 => {
-                let x = self.pop();
                 let y = self.pop();
+                let x = self.pop();
                 *self.get_tag_mut(x) = y;
-                self.push(y, PAIR_TAG);
+                self.push(y, PAIR_TAG)?;
             }
             Primitive::Equal => {
-                self.operate_comparison(|x, y| x == y);
+                let y = self.pop();
+                let x = self.pop();
+                self.push(self.get_boolean(x == y), PAIR_TAG)?;
             }
             Primitive::LessThan => {
-                self.operate_comparison(|x, y| x < y);
+                self.operate_comparison(|x, y| x < y)?;
             }
             Primitive::Add => {
-                self.operate_binary(Add::add);
+                self.operate_binary(i64::checked_add)?;
             }
             Primitive::Subtract => {
-                self.operate_binary(Sub::sub);
+                self.operate_binary(i64::checked_sub)?;
             }
             Primitive::Multiply => {
-                self.operate_binary(Mul::mul);
+                self.operate_binary(i64::checked_mul)?;
             }
             Primitive::Divide => {
-                self.operate_binary(Div::div);
+                if self.get_tos() == ZERO {
+                    return Err(Error::DivisionByZero);
+                }
+
+                self.operate_binary(i64::checked_div)?;
             }
             Primitive::GetC => {
                 let mut buffer = [0u8];
 
-                // TODO Handle errors.
-                stdin().read_exact(&mut buffer).unwrap();
+                let x = match self.reader.read_exact(&mut buffer) {
+                    Ok(()) => Object::Number(buffer[0] as u64),
+                    Err(error) if error.kind() == ErrorKind::UnexpectedEof => EOF,
+                    Err(error) => return Err(error.into()),
+                };
 
-                self.push(Object::Number(buffer[0] as u64), PAIR_TAG);
+                self.push(x, PAIR_TAG)?;
             }
             Primitive::PutC => {
                 let x = self.pop();
 
-                print!("{}", x.to_raw() as u8 as char);
+                self.writer
+                    .write_all(&[x.to_raw() as u8])
+                    .map_err(Error::from)?;
+                self.push(x, PAIR_TAG)?;
             }
         }
-    }
 
-    fn operate_binary(&mut self, operate: fn(u64, u64) -> u64) {
-        let x = self.pop().to_raw();
-        let y = self.pop().to_raw();
-
-        self.push(Object::Number(operate(x, y)), PAIR_TAG);
+        Ok(())
     }
 
-    fn operate_comparison(&mut self, operate: fn(u64, u64) -> bool) {
-        let x = self.pop().to_raw();
-        let y = self.pop().to_raw();
-
-        self.push(self.get_boolean(operate(x, y)), PAIR_TAG);
+    fn check_operands(&self, primitive: Primitive) -> Result<(), Error> {
+        match primitive {
+            Primitive::Close | Primitive::Field0 | Primitive::Field1 | Primitive::Field2 => {
+                self.check_operand(primitive, 0, ObjectType::Rib)
+            }
+            Primitive::SetField0 | Primitive::SetField1 | Primitive::SetField2 => {
+                self.check_operand(primitive, 1, ObjectType::Rib)
+            }
+            Primitive::LessThan
+            | Primitive::Add
+            | Primitive::Subtract
+            | Primitive::Multiply
+            | Primitive::Divide => {
+                self.check_operand(primitive, 0, ObjectType::Number)?;
+                self.check_operand(primitive, 1, ObjectType::Number)
+            }
+            Primitive::PutC => self.check_operand(primitive, 0, ObjectType::Number),
+            Primitive::Rib
+            | Primitive::Id
+            | Primitive::Pop
+            | Primitive::Skip
+            | Primitive::IsRib
+            | Primitive::Equal
+            | Primitive::GetC => Ok(()),
+        }
     }
 
-    // Garbage collection
+    fn check_operand(
+        &self,
+        primitive: Primitive,
+        index: u64,
+        expected: ObjectType,
+    ) -> Result<(), Error> {
+        let operand = self.get_car(self.get_list_tail(self.stack, Object::Number(index)));
 
-    #[allow(dead_code)]
-    fn collect_garbages(&mut self) {
-        let to_space = if self.allocation_limit == HEAP_MIDDLE {
-            HEAP_MIDDLE
+        if operand.r#type() == expected {
+            Ok(())
         } else {
-            HEAP_BOTTOM
-        };
+            Err(Error::TypeMismatch {
+                primitive,
+                expected,
+            })
+        }
+    }
 
-        self.allocation_limit = to_space + SPACE_SIZE;
-        self.allocation_index = to_space;
+    fn operate_host_primitive(&mut self, index: u64, argument_count: Object) -> Result<(), Error> {
+        let arity = self
+            .primitives
+            .get(&index)
+            .ok_or(Error::IllegalPrimitive(index))?
+            .arity;
+
+        if argument_count.to_raw() != arity as u64 {
+            return Err(Error::ArgumentCount {
+                procedure: self.get_procedure_name(),
+                expected: arity,
+                variadic: false,
+                actual: argument_count.to_raw() as usize,
+            });
+        }
 
-        todo!()
-    }
+        self.trace(|tracer| tracer.host_primitive(index));
 
-    // Input decoding
+        let mut arguments = (0..arity).map(|_| self.pop()).collect::<Vec<_>>();
+        arguments.reverse();
 
-    fn decode_symbols(&mut self) {
-        // Initialize non-printable symbols.
-        for _ in 0..self.read_integer(0) {
-            self.initialize_symbol(self.get_nil());
+        // The primitive is guaranteed to exist above.
+        let value = (self.primitives.get_mut(&index).unwrap().procedure)(&arguments);
+
+        if value.is_rib() && !self.is_allocated(value) {
+            return Err(Error::IllegalObject(value));
         }
 
-        // Symbol names are encoded in a reversed order.
+        self.push(value, PAIR_TAG)
+    }
+
+    fn is_allocated(&self, rib: Object) -> bool {
+        let start = self.allocation_limit - self.space_size;
+        let index = rib.to_raw() as usize;
+
+        (start..self.allocation_index).contains(&index)
+            && (index - start).is_multiple_of(rib::FIELD_COUNT)
+    }
+
+    // Numbers are signed integers stored in two's complement.
+    fn operate_binary(&mut self, operate: fn(i64, i64) -> Option<i64>) -> Result<(), Error> {
+        let y = self.pop().to_raw() as i64;
+        let x = self.pop().to_raw() as i64;
+
+        let z = operate(x, y).ok_or(Error::ArithmeticOverflow)?;
+        self.push(Object::Number(z as u64), PAIR_TAG)?;
+
+        Ok(())
+    }
+
+    fn operate_comparison(&mut self, operate: fn(i64, i64) -> bool) -> Result<(), Error> {
+        let y = self.pop().to_raw() as i64;
+        let x = self.pop().to_raw() as i64;
+
+        self.push(self.get_boolean(operate(x, y)), PAIR_TAG)?;
+
+        Ok(())
+    }
+
+    // Garbage collection
+
+    fn collect_garbages(&mut self) {
+        let heap_middle = HEAP_BOTTOM + self.space_size;
+        let to_space = if self.allocation_limit == heap_middle {
+            heap_middle
+        } else {
+            HEAP_BOTTOM
+        };
+
+        self.allocation_limit = to_space + self.space_size;
+        self.allocation_index = to_space;
+
+        self.stack = self.copy_object(self.stack);
+        self.program_counter = self.copy_object(self.program_counter);
+        self.r#false = self.copy_object(self.r#false);
+        self.symbol_table = self.copy_object(self.symbol_table);
+
+        for index in 0..self.host_roots.len() {
+            self.host_roots[index] = self.copy_object(self.host_roots[index]);
+        }
+
+        self.scan = to_space;
+
+        while self.scan < self.allocation_index {
+            self.heap[self.scan] = self.copy_object(self.heap[self.scan]);
+            self.scan += 1;
+        }
+
+        let live_objects = (self.allocation_index - to_space) / rib::FIELD_COUNT;
+        self.trace(|tracer| tracer.collect_garbages(live_objects));
+    }
+
+    fn copy_object(&mut self, object: Object) -> Object {
+        let Object::Rib(index) = object else {
+            return object;
+        };
+        let index = index as usize;
+
+        // A rib whose car points to a to-space is already copied and the car is a
+        // forwarding pointer.
+        if let Object::Rib(forward) = self.heap[index] {
+            if self.is_in_to_space(forward as usize) {
+                return Object::Rib(forward);
+            }
+        }
+
+        let copy = self.allocation_index;
+
+        self.heap.copy_within(index..index + rib::FIELD_COUNT, copy);
+        self.allocation_index += rib::FIELD_COUNT;
+        self.heap[index] = Object::Rib(copy as u64);
+
+        Object::Rib(copy as u64)
+    }
+
+    fn is_in_to_space(&self, index: usize) -> bool {
+        (self.allocation_limit - self.space_size..self.allocation_limit).contains(&index)
+    }
+
+    // Input decoding
+
+    fn decode_symbols(&mut self) -> Result<(), Error> {
+        // Initialize non-printable symbols.
+        for _ in 0..self.read_integer(0)? {
+            self.initialize_symbol(self.get_nil())?;
+        }
+
+        // Symbol names are encoded in a reversed order.
         let mut name = self.get_nil();
 
         loop {
-            match self.read_byte() {
+            match self.read_byte()? {
                 b',' => {
-                    self.initialize_symbol(name);
+                    self.initialize_symbol(name)?;
                     name = self.get_nil();
                 }
                 b';' => break,
                 character => {
-                    name = self.allocate_rib(Object::Number(character as u64), name, PAIR_TAG);
+                    name = self.allocate_rib(Object::Number(character as u64), name, PAIR_TAG)?;
                 }
             }
         }
 
-        self.initialize_symbol(name);
+        self.initialize_symbol(name)?;
+
+        Ok(())
     }
 
-    fn initialize_symbol(&mut self, name: Object) {
+    fn initialize_symbol(&mut self, name: Object) -> Result<(), Error> {
         let len = self.get_list_length(name);
-        let list = self.allocate_rib(name, len, STRING_TAG);
-        let symbol = self.allocate_rib(self.r#false, list, SYMBOL_TAG);
+        let list = self.allocate_rib(name, len, STRING_TAG)?;
+        let symbol = self.allocate_symbol(list)?;
+
+        self.symbol_table = self.allocate_rib(symbol, self.symbol_table, PAIR_TAG)?;
 
-        self.symbol_table = self.allocate_rib(symbol, self.symbol_table, PAIR_TAG);
+        Ok(())
     }
 
-    fn decode_codes(&mut self) {
+    fn allocate_symbol(&mut self, string: Object) -> Result<Object, Error> {
+        self.allocate_rib(self.r#false, string, SYMBOL_TAG)
+    }
+
+    fn decode_codes(&mut self) -> Result<(), Error> {
         let mut n;
         let mut d;
         let mut op;
 
         loop {
-            let x = self.read_code();
+            let position = self.position;
+            let x = self.read_code()?;
             n = Object::Number(x as u64);
             op = -1;
 
             while n.to_raw() > {
                 op += 1;
-                d = INSTRUCTION_WEIGHTS[op as usize];
+                d = *INSTRUCTION_WEIGHTS
+                    .get(op as usize)
+                    .ok_or(Error::MalformedCode(position))?;
                 d + 2
             } {
                 n = Object::Number(n.to_raw() - d - 3);
@@ -577,29 +1239,31 @@ impl<'a> Vm<'a> {
 
             if x > 90 {
                 op = Instruction::If as i64;
-                n = self.pop();
+                n = self.pop_code(position)?;
             } else {
                 if op == 0 {
-                    self.push(ZERO, ZERO);
+                    self.push(ZERO, ZERO)?;
                 }
 
                 n = if n.to_raw() == d {
-                    Object::Number(self.read_integer(0) as u64)
+                    Object::Number(self.read_integer(0)? as u64)
                 } else if n.to_raw() > d {
-                    let integer = self.read_integer((n.to_raw() - d - 1) as i64);
+                    let integer = self.read_integer((n.to_raw() - d - 1) as i64)?;
                     self.get_symbol_ref(Object::Number(integer as u64))
+                        .ok_or(Error::MalformedCode(position))?
                 } else if op < 3 {
                     self.get_symbol_ref(n)
+                        .ok_or(Error::MalformedCode(position))?
                 } else {
                     n
                 };
 
                 if op > 4 {
-                    let object = self.pop();
-                    let rib2 = self.allocate_rib2(n, ZERO, object);
-                    n = self.allocate_rib(rib2, self.get_nil(), CLOSURE_TAG);
+                    let object = self.pop_code(position)?;
+                    let rib2 = self.allocate_rib2(n, ZERO, object)?;
+                    n = self.allocate_rib(rib2, self.get_nil(), CLOSURE_TAG)?;
 
-                    if self.stack.to_raw() == ZERO.to_raw() {
+                    if self.stack == ZERO {
                         break;
                     }
 
@@ -612,42 +1276,60 @@ impl<'a> Vm<'a> {
                 }
             }
 
-            #[cfg(feature = "trace")]
-            println!("decode: {} {}", op, x);
+            if let Some(instruction) = Instruction::from_i64(op) {
+                self.trace(|tracer| tracer.decode(position, instruction, n));
+            }
+
+            if !self.stack.is_rib() {
+                return Err(Error::MalformedCode(position));
+            }
 
             // TODO Review this.
-            let instruction = self.allocate_rib(Object::Number(op as u64), n, ZERO);
+            let instruction = self.allocate_rib(Object::Number(op as u64), n, ZERO)?;
             *self.get_tag_mut(instruction) = self.get_tos();
             *self.get_tos_mut() = instruction;
         }
 
         self.program_counter = self.get_tag(self.get_car(n));
+
+        Ok(())
     }
 
-    fn read_byte(&mut self) -> u8 {
-        let byte = self.input[self.position];
+    fn pop_code(&mut self, position: usize) -> Result<Object, Error> {
+        if self.stack.is_rib() {
+            Ok(self.pop())
+        } else {
+            Err(Error::MalformedCode(position))
+        }
+    }
+
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        let byte = *self
+            .input
+            .get(self.position)
+            .ok_or(Error::UnexpectedEndOfInput(self.position))?;
         self.position += 1;
-        byte
+        Ok(byte)
     }
 
-    fn read_code(&mut self) -> i64 {
-        let x = self.read_byte() as i64 - 35;
+    fn read_code(&mut self) -> Result<i64, Error> {
+        let x = self.read_byte()? as i64 - 35;
 
-        if x < 0 {
-            57
-        } else {
-            x
-        }
+        Ok(if x < 0 { 57 } else { x })
     }
 
-    fn read_integer(&mut self, mut n: i64) -> i64 {
-        let x = self.read_code();
-        n *= 46;
+    fn read_integer(&mut self, n: i64) -> Result<i64, Error> {
+        let position = self.position;
+        let x = self.read_code()?;
+        let n = n
+            .checked_mul(46)
+            .and_then(|n| n.checked_add(if x < 46 { x } else { x - 46 }))
+            .ok_or(Error::IntegerOverflow(position))?;
 
         if x < 46 {
-            n + x
+            Ok(n)
         } else {
-            self.read_integer(n + x - 46)
+            self.read_integer(n)
         }
     }
 }
@@ -655,13 +1337,1179 @@ impl<'a> Vm<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        code::{Code, Constant, Operand, Procedure},
+        encoder::encode_program,
+    };
+    use std::{cell::Cell, io};
+
+    // (define x 42)
+    // spell-checker: disable-next-line
+    const DEFINE_GLOBAL: &[u8] = b"#di,!tes-1dleif,1gra,,,,bir;)lk>m?mki#!):nlkl!':nlkm!(:nlku{";
 
     #[test]
     fn define_global() {
-        // (define x 42)
+        Vm::new(DEFINE_GLOBAL).run().unwrap();
+    }
+
+    #[test]
+    fn step() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        assert_eq!(vm.instruction(), Some(Instruction::Constant));
+        assert_eq!(vm.stack().count(), 0);
+
+        assert_eq!(vm.step(), Ok(Status::Continue));
+
+        assert_eq!(vm.stack().collect::<Vec<_>>(), [Object::Number(10)]);
+
+        while vm.step().unwrap() == Status::Continue {}
+
+        assert_eq!(vm.instruction(), Some(Instruction::Halt));
+        assert_eq!(vm.step(), Ok(Status::Halted));
+    }
+
+    #[test]
+    fn fail_to_step_illegal_instruction() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        *vm.get_car_mut(vm.program_counter) = Object::Number(42);
+
+        assert_eq!(vm.step(), Err(Error::IllegalInstruction(42)));
+    }
+
+    // (subtract 50 8)
+    fn encode_host_primitive_call(argument_count: u64) -> String {
+        encode_program(
+            &["subtract"],
+            vec![
+                Code::Constant(Constant::Number(50)),
+                Code::Constant(Constant::Number(8)),
+                Code::Constant(Constant::Number(argument_count)),
+                Code::Apply(Operand::Symbol(4)),
+            ],
+        )
+    }
+
+    fn subtract(arguments: &[Object]) -> Object {
+        Object::Number(arguments[0].to_raw() - arguments[1].to_raw())
+    }
+
+    #[test]
+    fn register_primitive() {
+        let input = encode_host_primitive_call(2);
+        let mut vm = Vm::new(input.as_bytes());
+
+        vm.register_primitive(100, "subtract", 2, subtract).unwrap();
+        vm.run().unwrap();
+
+        assert_eq!(vm.pop(), Object::Number(42));
+    }
+
+    #[test]
+    fn register_primitive_with_new_name() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        vm.register_primitive(100, "subtract", 2, subtract).unwrap();
+
+        let procedure = vm.global("subtract").unwrap();
+
+        assert_eq!(vm.get_car(procedure), Object::Number(100));
+        assert_eq!(
+            vm.call(procedure, &[Object::Number(50), Object::Number(8)]),
+            Ok(Object::Number(42))
+        );
+    }
+
+    #[test]
+    fn fail_to_return_unallocated_rib_from_primitive() {
+        let input = encode_host_primitive_call(2);
+        let mut vm = Vm::new(input.as_bytes());
+
+        vm.register_primitive(100, "subtract", 2, |_| Object::Rib(1 << 40))
+            .unwrap();
+
+        assert_eq!(vm.run(), Err(Error::IllegalObject(Object::Rib(1 << 40))));
+    }
+
+    #[test]
+    fn fail_to_register_builtin_primitive() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        assert_eq!(
+            vm.register_primitive(Primitive::Add as u64, "subtract", 2, subtract),
+            Err(Error::IllegalPrimitive(Primitive::Add as u64))
+        );
+    }
+
+    #[test]
+    fn fail_to_call_primitive_with_wrong_argument_count() {
+        let input = encode_host_primitive_call(1);
+        let mut vm = Vm::new(input.as_bytes());
+
+        vm.register_primitive(100, "subtract", 2, subtract).unwrap();
+
+        assert_eq!(
+            vm.run(),
+            Err(Error::ArgumentCount {
+                procedure: Some("subtract".into()),
+                expected: 2,
+                variadic: false,
+                actual: 1,
+            })
+        );
+    }
+
+    // (lambda (x y) (- x y)) or (lambda (x y . _) (- x y))
+    fn subtract_procedure(variadic: bool) -> Procedure {
+        Procedure::new(
+            2,
+            variadic,
+            vec![
+                Code::Constant(Constant::Number(Primitive::Subtract as u64)),
+                Code::Constant(Constant::Number(0)),
+                Code::Constant(Constant::Number(1)),
+                Code::Constant(Constant::Number(3)),
+                Code::Apply(Operand::Symbol(0)),
+                Code::Get(Operand::Stack(1)),
+                Code::Get(Operand::Stack(3)),
+                Code::Constant(Constant::Number(2)),
+                Code::Apply(Operand::Stack(3)),
+            ],
+        )
+    }
+
+    // (define f (lambda (x y) (- x y)))
+    fn encode_procedure_definition() -> String {
+        encode_program(
+            &["f"],
+            vec![
+                Code::Constant(Constant::Procedure(subtract_procedure(false))),
+                Code::Set(Operand::Symbol(4)),
+                Code::Constant(Constant::Number(0)),
+                Code::Constant(Constant::Number(0)),
+                Code::Constant(Constant::Number(0)),
+                Code::Constant(Constant::Number(3)),
+                Code::Apply(Operand::Symbol(0)),
+            ],
+        )
+    }
+
+    #[test]
+    fn call_procedure() {
+        let input = encode_procedure_definition();
+        let mut vm = Vm::new(input.as_bytes());
+
+        vm.run().unwrap();
+
+        let stack = vm.stack().collect::<Vec<_>>();
+        let procedure = vm.global("f").unwrap();
+
+        assert_eq!(
+            vm.call(procedure, &[Object::Number(50), Object::Number(8)]),
+            Ok(Object::Number(42))
+        );
+        assert_eq!(vm.stack().collect::<Vec<_>>(), stack);
+        assert_eq!(vm.instruction(), Some(Instruction::Halt));
+    }
+
+    #[test]
+    fn keep_continuation_of_call_on_garbage_collection() {
+        let input = encode_procedure_definition();
+        let mut vm = Vm::new(input.as_bytes());
+
+        vm.run().unwrap();
+
+        let procedure = vm.global("f").unwrap();
+        let root = vm.host_roots.len();
+        vm.host_roots
+            .extend([procedure, Object::Number(50), Object::Number(8)]);
+
+        // Let an allocation of a call instruction collect garbages after arguments,
+        // an argument count, and a halt instruction are allocated.
+        while vm.allocation_index != vm.allocation_limit - 5 * rib::FIELD_COUNT {
+            vm.allocate_rib(ZERO, ZERO, PAIR_TAG).unwrap();
+        }
+
+        vm.initialize_call(root, 2).unwrap();
+
+        let continuation = vm.get_tag(vm.program_counter);
+
+        assert!(vm.is_allocated(continuation));
+        assert_eq!(vm.get_car(continuation), Object::Number(Instruction::HALT));
+    }
+
+    #[test]
+    fn fail_to_call_procedure_with_wrong_argument_count() {
+        let input = encode_procedure_definition();
+        let mut vm = Vm::new(input.as_bytes());
+
+        vm.run().unwrap();
+
+        let procedure = vm.global("f").unwrap();
+
+        assert_eq!(
+            vm.call(procedure, &[Object::Number(50)]),
+            Err(Error::ArgumentCount {
+                procedure: None,
+                expected: 2,
+                variadic: false,
+                actual: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn call_procedure_after_failure() {
+        let input = encode_procedure_definition();
+        let mut vm = Vm::new(input.as_bytes());
+
+        vm.run().unwrap();
+
+        let stack = vm.stack().collect::<Vec<_>>();
+        let procedure = vm.global("f").unwrap();
+
+        assert!(vm.call(procedure, &[Object::Number(50)]).is_err());
+        assert_eq!(vm.stack().collect::<Vec<_>>(), stack);
+        assert_eq!(vm.instruction(), Some(Instruction::Halt));
+
+        let procedure = vm.global("f").unwrap();
+
+        assert_eq!(
+            vm.call(procedure, &[Object::Number(50), Object::Number(8)]),
+            Ok(Object::Number(42))
+        );
+    }
+
+    #[test]
+    fn call_procedure_repeatedly() {
+        let input = encode_procedure_definition();
+        let mut vm = Vm::builder()
+            .max_objects(1 << 7)
+            .build(input.as_bytes())
+            .unwrap();
+
+        vm.run().unwrap();
+
+        for index in 0..1000 {
+            let procedure = vm.global("f").unwrap();
+
+            assert_eq!(
+                vm.call(
+                    procedure,
+                    &[Object::Number(index + 1), Object::Number(index)]
+                ),
+                Ok(Object::Number(1))
+            );
+        }
+    }
+
+    // (define f (lambda (x y . _) (- x y)))
+    // (define g (lambda (x) (f x 8)))
+    fn encode_nested_procedure_definition() -> String {
+        encode_program(
+            &["f", "g"],
+            vec![
+                Code::Constant(Constant::Procedure(subtract_procedure(true))),
+                Code::Set(Operand::Symbol(4)),
+                Code::Constant(Constant::Procedure(Procedure::new(
+                    1,
+                    false,
+                    vec![
+                        Code::Get(Operand::Stack(0)),
+                        Code::Constant(Constant::Number(8)),
+                        Code::Constant(Constant::Number(2)),
+                        Code::Apply(Operand::Symbol(4)),
+                    ],
+                ))),
+                Code::Set(Operand::Symbol(5)),
+                Code::Constant(Constant::Number(0)),
+                Code::Constant(Constant::Number(0)),
+                Code::Constant(Constant::Number(0)),
+                Code::Constant(Constant::Number(3)),
+                Code::Apply(Operand::Symbol(0)),
+            ],
+        )
+    }
+
+    #[test]
+    fn call_procedure_after_out_of_memory_in_nested_call() {
+        const MAX_OBJECTS: usize = 1 << 7;
+
+        let input = encode_nested_procedure_definition();
+        let mut vm = Vm::builder()
+            .max_objects(MAX_OBJECTS)
+            .build(input.as_bytes())
+            .unwrap();
+
+        vm.run().unwrap();
+
+        let mut failures = 0;
+
+        // Fill a heap with live objects of different sizes so that calls run out
+        // of memory at different allocations.
+        for count in 0..MAX_OBJECTS {
+            let root = vm.host_roots.len();
+            vm.host_roots.push(ZERO);
+
+            for _ in 0..count {
+                let Ok(rib) = vm.allocate_rib(vm.host_roots[root], ZERO, PAIR_TAG) else {
+                    break;
+                };
+                vm.host_roots[root] = rib;
+            }
+
+            let procedure = vm.global("g").unwrap();
+
+            if vm.call(procedure, &[Object::Number(50)]) == Err(Error::OutOfMemory) {
+                failures += 1;
+            }
+
+            vm.host_roots.truncate(root);
+
+            let procedure = vm.global("g").unwrap();
+
+            assert_eq!(
+                vm.call(procedure, &[Object::Number(50)]),
+                Ok(Object::Number(42))
+            );
+        }
+
+        assert!(failures > 0);
+    }
+
+    #[test]
+    fn call_primitive_with_rib() {
+        let mut vm = Vm::builder()
+            .max_objects(1 << 7)
+            .build(DEFINE_GLOBAL)
+            .unwrap();
+
+        vm.run().unwrap();
+
+        for index in 0..1000 {
+            let procedure = vm
+                .allocate_rib(Object::Number(Primitive::Id as u64), ZERO, CLOSURE_TAG)
+                .unwrap();
+            let argument = vm
+                .allocate_rib(Object::Number(index), procedure, PAIR_TAG)
+                .unwrap();
+
+            let result = vm.call(vm.get_cdr(argument), &[argument]).unwrap();
+
+            assert_eq!(vm.get_car(result), Object::Number(index));
+            assert_eq!(vm.get_tag(vm.get_cdr(result)), CLOSURE_TAG);
+        }
+    }
+
+    #[test]
+    fn fail_to_call_number() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        assert_eq!(vm.call(ZERO, &[]), Err(Error::IllegalProcedure(ZERO)));
+    }
+
+    // ((rib id 0 1) x)
+    fn encode_global_reference() -> String {
+        encode_program(
+            &["x"],
+            vec![
+                Code::Constant(Constant::Number(Primitive::Id as u64)),
+                Code::Constant(Constant::Number(0)),
+                Code::Constant(Constant::Number(1)),
+                Code::Constant(Constant::Number(3)),
+                Code::Apply(Operand::Symbol(0)),
+                Code::Get(Operand::Symbol(4)),
+                Code::Constant(Constant::Number(1)),
+                Code::Apply(Operand::Stack(2)),
+            ],
+        )
+    }
+
+    #[test]
+    fn set_global() {
+        let input = encode_global_reference();
+        let mut vm = Vm::new(input.as_bytes());
+
+        vm.set_global("x", Object::Number(42)).unwrap();
+
+        assert_eq!(vm.global("x"), Some(Object::Number(42)));
+
+        vm.run().unwrap();
+
+        assert_eq!(vm.pop(), Object::Number(42));
+    }
+
+    #[test]
+    fn get_global() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        assert_eq!(vm.global("id"), Some(vm.r#false));
+
+        vm.run().unwrap();
+
+        let procedure = vm.global("id").unwrap();
+
+        assert_eq!(vm.get_tag(procedure), CLOSURE_TAG);
+        assert_eq!(
+            vm.call(procedure, &[Object::Number(42)]),
+            Ok(Object::Number(42))
+        );
+    }
+
+    #[test]
+    fn fail_to_access_undefined_global() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        assert_eq!(vm.global("foo"), None);
+        assert_eq!(
+            vm.set_global("foo", Object::Number(42)),
+            Err(Error::IllegalSymbol("foo".into()))
+        );
+    }
+
+    #[test]
+    fn access_built_in_global() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        let rib = vm.global("rib").unwrap();
+
+        assert_eq!(vm.get_tag(rib), CLOSURE_TAG);
+        assert_eq!(vm.get_car(rib), ZERO);
+
+        vm.set_global("rib", Object::Number(42)).unwrap();
+
+        assert_eq!(vm.global("rib"), Some(Object::Number(42)));
+    }
+
+    #[test]
+    fn access_global_defined_in_scheme() {
+        let mut vm = Vm::builder()
+            .reader(b"(define foo 42)\n".as_slice())
+            .writer(io::sink())
+            .build(REPL)
+            .unwrap();
+
+        vm.run().unwrap();
+
+        assert_eq!(vm.global("foo"), Some(Object::Number(42)));
+
+        vm.set_global("foo", Object::Number(13)).unwrap();
+
+        assert_eq!(vm.global("foo"), Some(Object::Number(13)));
+    }
+
+    #[test]
+    fn intern_existing_symbol() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        assert_eq!(vm.intern("id"), Ok(vm.find_symbol("id").unwrap()));
+    }
+
+    #[test]
+    fn intern_built_in_symbol() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+        let length = vm.get_list_length(vm.symbol_table).to_raw();
+        let rib = vm.global("rib").unwrap();
+
+        let symbol = vm.intern("rib").unwrap();
+
+        assert_eq!(vm.get_car(symbol), rib);
+        assert_eq!(vm.get_list_length(vm.symbol_table).to_raw(), length);
+        assert_eq!(vm.global("rib"), Some(rib));
+    }
+
+    #[test]
+    fn intern_new_symbol() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+        let length = vm.get_list_length(vm.symbol_table).to_raw();
+
+        let symbol = vm.intern("foo").unwrap();
+
+        assert_eq!(vm.get_tag(symbol), SYMBOL_TAG);
+        assert_eq!(vm.get_car(symbol), vm.r#false);
+        assert_eq!(vm.get_symbol_name(symbol).as_deref(), Some("foo"));
+        assert_eq!(vm.intern("foo"), Ok(symbol));
+        assert_eq!(vm.get_list_length(vm.symbol_table).to_raw(), length + 1);
+    }
+
+    #[test]
+    fn intern_symbol_for_scheme() {
+        let mut output = vec![];
+        let mut vm = Vm::builder()
+            .reader(b"foo\n".as_slice())
+            .writer(&mut output)
+            .build(REPL)
+            .unwrap();
+
+        let symbol = vm.intern("foo").unwrap();
+        *vm.get_car_mut(symbol) = Object::Number(42);
+
+        vm.run().unwrap();
+
+        drop(vm);
+
+        assert_eq!(output, b"> 42\n> ");
+    }
+
+    #[test]
+    fn intern_symbol_from_scheme() {
+        let mut vm = Vm::builder()
+            .reader(b"(define foo 42)\n".as_slice())
+            .writer(io::sink())
+            .build(REPL)
+            .unwrap();
+
+        vm.run().unwrap();
+
+        let symbol = vm.intern("foo").unwrap();
+
+        assert_eq!(vm.get_car(symbol), Object::Number(42));
+        assert_eq!(vm.intern("foo"), Ok(symbol));
+    }
+
+    #[test]
+    fn intern_symbols_with_garbage_collection() {
+        let mut vm = Vm::builder()
+            .max_objects(1 << 8)
+            .build(DEFINE_GLOBAL)
+            .unwrap();
+        let length = vm.get_list_length(vm.symbol_table).to_raw();
+
+        for index in 0..1000 {
+            let name = (index % 10).to_string();
+            let symbol = vm.intern(&name).unwrap();
+
+            assert_eq!(vm.get_symbol_name(symbol), Some(name));
+
+            vm.allocate_rib(ZERO, ZERO, PAIR_TAG).unwrap();
+        }
+
+        assert_eq!(vm.get_list_length(vm.symbol_table).to_raw(), length + 10);
+    }
+
+    #[test]
+    fn run_with_fuel() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        assert_eq!(vm.run_with_fuel(0), Ok(Status::OutOfFuel));
+        assert_eq!(vm.run_with_fuel(10), Ok(Status::OutOfFuel));
+        assert_eq!(vm.run_with_fuel(16), Ok(Status::OutOfFuel));
+        assert_eq!(vm.run_with_fuel(1), Ok(Status::Halted));
+        assert_eq!(vm.run_with_fuel(1), Ok(Status::Halted));
+
+        let mut other = Vm::new(DEFINE_GLOBAL);
+        other.run().unwrap();
+
+        assert_eq!(vm.stack, other.stack);
+        assert_eq!(vm.heap, other.heap);
+    }
+
+    #[test]
+    fn resume_repl_with_fuel() {
+        let mut output = vec![];
+        let mut vm = Vm::builder()
+            .reader(b"(+ 1 2)\n".as_slice())
+            .writer(&mut output)
+            .build(REPL)
+            .unwrap();
+
+        while vm.run_with_fuel(1000).unwrap() == Status::OutOfFuel {}
+
+        drop(vm);
+
+        assert_eq!(output, b"> 3\n> ");
+    }
+
+    // A tracer counting garbage collections in a cell readable while a virtual
+    // machine holds the tracer.
+    struct CollectionTracer<'a>(&'a Cell<usize>);
+
+    impl Tracer for CollectionTracer<'_> {
+        fn collect_garbages(&mut self, _: usize) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[derive(Default)]
+    struct CountTracer {
+        decodes: usize,
+        instructions: usize,
+        primitives: Vec<Primitive>,
+        host_primitives: usize,
+        allocations: usize,
+        collections: usize,
+    }
+
+    impl Tracer for CountTracer {
+        fn decode(&mut self, _: usize, _: Instruction, _: Object) {
+            self.decodes += 1;
+        }
+
+        fn instruction(&mut self, _: Instruction, _: Object) {
+            self.instructions += 1;
+        }
+
+        fn primitive(&mut self, primitive: Primitive) {
+            self.primitives.push(primitive);
+        }
+
+        fn allocate(&mut self, _: Object) {
+            self.allocations += 1;
+        }
+
+        fn host_primitive(&mut self, _: u64) {
+            self.host_primitives += 1;
+        }
+
+        fn collect_garbages(&mut self, _: usize) {
+            self.collections += 1;
+        }
+    }
+
+    #[test]
+    fn trace_host_primitive() {
+        let input = encode_host_primitive_call(2);
+        let mut tracer = CountTracer::default();
+        let mut vm = Vm::builder()
+            .tracer(&mut tracer)
+            .build(input.as_bytes())
+            .unwrap();
+
+        vm.register_primitive(100, "subtract", 2, subtract).unwrap();
+        vm.run().unwrap();
+
+        drop(vm);
+
+        assert_eq!(tracer.host_primitives, 1);
+    }
+
+    #[test]
+    fn trace() {
+        let mut tracer = CountTracer::default();
+
+        Vm::builder()
+            .max_objects(100)
+            .tracer(&mut tracer)
+            .build(DEFINE_GLOBAL)
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(tracer.decodes, 27);
+        assert_eq!(tracer.instructions, 28);
+        assert_eq!(tracer.primitives[0], Primitive::Rib);
+        assert_eq!(tracer.allocations, 103);
+        assert_eq!(tracer.collections, 1);
+    }
+
+    #[test]
+    fn apply_variadic_procedure() {
+        // ((lambda xs (rib xs 0 0)) 1 2 3)
+        let mut vm = Vm::new(b"#,,,bir;7'nnml#nkk^zy");
+
+        vm.run().unwrap();
+
+        let list = vm.get_car(vm.get_car(vm.stack));
+        assert_eq!(vm.get_list_length(list), Object::Number(3));
+
+        for (index, number) in [1, 2, 3].into_iter().enumerate() {
+            assert_eq!(
+                vm.get_car(vm.get_list_tail(list, Object::Number(index as u64))),
+                Object::Number(number)
+            );
+        }
+    }
+
+    #[test]
+    fn apply_variadic_procedure_without_rest_arguments() {
+        // ((lambda (x . xs) (rib xs 0 0)) 1)
+        let mut vm = Vm::new(b"#,,,bir;7%ll#nkk_|y");
+
+        vm.run().unwrap();
+
+        let list = vm.get_car(vm.get_car(vm.stack));
+        assert_eq!(list, vm.get_nil());
+    }
+
+    #[test]
+    fn write_characters() {
+        let mut output = vec![];
+
         // spell-checker: disable-next-line
-        Vm::new(b"#di,!tes-1dleif,1gra,,,,bir;)lk>m?mki#!):nlkl!':nlkm!(:nlku{")
+        Vm::builder()
+            .writer(&mut output)
+            .build(b"#,,,bir;7&lvR7X%lvR6:nlkv6y")
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(output, b"AB");
+    }
+
+    // ((rib primitive 0 1) x y)
+    fn encode_binary_operation(primitive: Primitive, x: u64, y: u64) -> String {
+        encode_program(
+            &[],
+            vec![
+                Code::Constant(Constant::Number(primitive as u64)),
+                Code::Constant(Constant::Number(0)),
+                Code::Constant(Constant::Number(1)),
+                Code::Constant(Constant::Number(3)),
+                Code::Apply(Operand::Symbol(0)),
+                Code::Constant(Constant::Number(x)),
+                Code::Constant(Constant::Number(y)),
+                Code::Constant(Constant::Number(2)),
+                Code::Apply(Operand::Stack(3)),
+            ],
+        )
+    }
+
+    fn run_binary_operation(primitive: Primitive, x: u64, y: u64) -> Result<Object, Error> {
+        let input = encode_binary_operation(primitive, x, y);
+        let mut vm = Vm::new(input.as_bytes());
+
+        vm.run()?;
+
+        Ok(vm.pop())
+    }
+
+    #[test]
+    fn subtract_to_negative_number() {
+        assert_eq!(
+            run_binary_operation(Primitive::Subtract, 1, 2),
+            Ok(Object::Number(-1i64 as u64))
+        );
+    }
+
+    #[test]
+    fn compare_negative_number() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        vm.push(Object::Number(-1i64 as u64), PAIR_TAG).unwrap();
+        vm.push(ZERO, PAIR_TAG).unwrap();
+        vm.operate_primitive(Primitive::LessThan).unwrap();
+
+        assert_eq!(vm.pop(), vm.get_true());
+    }
+
+    #[test]
+    fn compare_numbers_in_order() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        vm.push(Object::Number(1), PAIR_TAG).unwrap();
+        vm.push(Object::Number(2), PAIR_TAG).unwrap();
+        vm.operate_primitive(Primitive::LessThan).unwrap();
+
+        assert_eq!(vm.pop(), vm.get_true());
+    }
+
+    #[test]
+    fn divide_numbers_in_order() {
+        assert_eq!(
+            run_binary_operation(Primitive::Divide, 42, 2),
+            Ok(Object::Number(21))
+        );
+    }
+
+    #[test]
+    fn create_rib_with_fields_in_order() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        for number in 1..=3 {
+            vm.push(Object::Number(number), PAIR_TAG).unwrap();
+        }
+
+        vm.operate_primitive(Primitive::Rib).unwrap();
+        let rib = vm.pop();
+
+        assert_eq!(vm.get_car(rib), Object::Number(1));
+        assert_eq!(vm.get_cdr(rib), Object::Number(2));
+        assert_eq!(vm.get_tag(rib), Object::Number(3));
+    }
+
+    #[test]
+    fn set_fields_of_rib() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        for (set_field, get_field) in [
+            (Primitive::SetField0, Primitive::Field0),
+            (Primitive::SetField1, Primitive::Field1),
+            (Primitive::SetField2, Primitive::Field2),
+        ] {
+            let rib = vm.allocate_rib(ZERO, ZERO, ZERO).unwrap();
+
+            vm.push(rib, PAIR_TAG).unwrap();
+            vm.push(Object::Number(42), PAIR_TAG).unwrap();
+            vm.operate_primitive(set_field).unwrap();
+
+            assert_eq!(vm.pop(), Object::Number(42));
+
+            vm.push(rib, PAIR_TAG).unwrap();
+            vm.operate_primitive(get_field).unwrap();
+
+            assert_eq!(vm.pop(), Object::Number(42));
+        }
+    }
+
+    #[test]
+    fn compare_objects_for_equality() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        vm.push(vm.r#false, PAIR_TAG).unwrap();
+        vm.push(vm.r#false, PAIR_TAG).unwrap();
+        vm.operate_primitive(Primitive::Equal).unwrap();
+
+        assert_eq!(vm.pop(), vm.get_true());
+
+        vm.push(vm.r#false, PAIR_TAG).unwrap();
+        vm.push(vm.get_nil(), PAIR_TAG).unwrap();
+        vm.operate_primitive(Primitive::Equal).unwrap();
+
+        assert_eq!(vm.pop(), vm.r#false);
+    }
+
+    #[test]
+    fn write_character_and_return_it() {
+        let mut output = vec![];
+        let mut vm = Vm::builder()
+            .writer(&mut output)
+            .build(DEFINE_GLOBAL)
+            .unwrap();
+
+        vm.push(Object::Number(b'A' as u64), PAIR_TAG).unwrap();
+        vm.operate_primitive(Primitive::PutC).unwrap();
+
+        assert_eq!(vm.pop(), Object::Number(b'A' as u64));
+
+        drop(vm);
+
+        assert_eq!(output, b"A");
+    }
+
+    #[test]
+    fn fail_to_add_with_overflow() {
+        assert_eq!(
+            run_binary_operation(Primitive::Add, i64::MAX as u64, 1),
+            Err(Error::ArithmeticOverflow)
+        );
+    }
+
+    #[test]
+    fn fail_to_multiply_with_overflow() {
+        assert_eq!(
+            run_binary_operation(Primitive::Multiply, i64::MAX as u64, 2),
+            Err(Error::ArithmeticOverflow)
+        );
+    }
+
+    #[test]
+    fn fail_to_divide_by_zero() {
+        assert_eq!(
+            run_binary_operation(Primitive::Divide, 1, 0),
+            Err(Error::DivisionByZero)
+        );
+    }
+
+    #[test]
+    fn fail_to_get_field_of_number() {
+        let mut vm = Vm::builder().checked(true).build(DEFINE_GLOBAL).unwrap();
+
+        vm.push(Object::Number(42), PAIR_TAG).unwrap();
+
+        assert_eq!(
+            vm.operate_primitive(Primitive::Field0),
+            Err(Error::TypeMismatch {
+                primitive: Primitive::Field0,
+                expected: ObjectType::Rib
+            })
+        );
+    }
+
+    #[test]
+    fn fail_to_set_field_of_number() {
+        let mut vm = Vm::builder().checked(true).build(DEFINE_GLOBAL).unwrap();
+
+        vm.push(Object::Number(42), PAIR_TAG).unwrap();
+        vm.push(vm.r#false, PAIR_TAG).unwrap();
+
+        assert_eq!(
+            vm.operate_primitive(Primitive::SetField1),
+            Err(Error::TypeMismatch {
+                primitive: Primitive::SetField1,
+                expected: ObjectType::Rib
+            })
+        );
+    }
+
+    #[test]
+    fn fail_to_add_rib() {
+        let mut vm = Vm::builder().checked(true).build(DEFINE_GLOBAL).unwrap();
+
+        vm.push(vm.r#false, PAIR_TAG).unwrap();
+        vm.push(Object::Number(42), PAIR_TAG).unwrap();
+
+        assert_eq!(
+            vm.operate_primitive(Primitive::Add),
+            Err(Error::TypeMismatch {
+                primitive: Primitive::Add,
+                expected: ObjectType::Number
+            })
+        );
+    }
+
+    #[test]
+    fn get_field_of_rib() {
+        let mut vm = Vm::builder().checked(true).build(DEFINE_GLOBAL).unwrap();
+
+        vm.push(vm.r#false, PAIR_TAG).unwrap();
+        vm.operate_primitive(Primitive::Field0).unwrap();
+
+        assert_eq!(vm.pop(), vm.get_true());
+    }
+
+    #[test]
+    fn fail_to_decode_empty_input() {
+        assert_eq!(Vm::try_new(b"").err(), Some(Error::UnexpectedEndOfInput(0)));
+    }
+
+    #[test]
+    fn fail_to_decode_truncated_input() {
+        assert_eq!(
+            Vm::try_new(&DEFINE_GLOBAL[..DEFINE_GLOBAL.len() - 1]).err(),
+            Some(Error::UnexpectedEndOfInput(DEFINE_GLOBAL.len() - 1))
+        );
+    }
+
+    #[test]
+    fn fail_to_decode_large_integer() {
+        assert_eq!(
+            Vm::try_new(&[b'~'; 16]).err(),
+            Some(Error::IntegerOverflow(11))
+        );
+    }
+
+    #[test]
+    fn fail_to_decode_if_without_branch() {
+        assert_eq!(
+            Vm::try_new(b"#,,,bir;~").err(),
+            Some(Error::MalformedCode(8))
+        );
+    }
+
+    #[test]
+    fn fail_to_decode_unknown_symbol() {
+        assert_eq!(
+            Vm::try_new(b"#,,,bir;'").err(),
+            Some(Error::MalformedCode(8))
+        );
+    }
+
+    #[test]
+    fn fail_to_decode_illegal_code() {
+        assert_eq!(
+            Vm::try_new(b"#,,,bir;\xff").err(),
+            Some(Error::MalformedCode(8))
+        );
+    }
+
+    #[test]
+    fn run_repl() {
+        let mut output = vec![];
+
+        Vm::builder()
+            .reader(b"(+ 1 2)\n".as_slice())
+            .writer(&mut output)
+            .build(REPL)
+            .unwrap()
             .run()
             .unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "> 3\n> ");
+    }
+
+    #[test]
+    fn collect_garbages() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        for _ in 0..3 {
+            vm.collect_garbages();
+        }
+
+        vm.run().unwrap();
+    }
+
+    #[test]
+    fn reclaim_garbages() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+
+        for _ in 0..vm.space_size {
+            vm.allocate_rib(ZERO, ZERO, PAIR_TAG).unwrap();
+        }
+
+        vm.run().unwrap();
+    }
+
+    #[test]
+    fn decode_with_small_heap() {
+        let collections = Cell::new(0);
+        let mut output = vec![];
+        let mut vm = Vm::builder()
+            .max_objects(6500)
+            .reader(b"(+ 1 2)\n".as_slice())
+            .writer(&mut output)
+            .tracer(CollectionTracer(&collections))
+            .build(REPL)
+            .unwrap();
+
+        assert!(collections.get() > 0);
+
+        vm.run().unwrap();
+
+        drop(vm);
+
+        assert_eq!(output, b"> 3\n> ");
+    }
+
+    #[test]
+    fn fail_to_build_without_heap() {
+        assert_eq!(
+            Vm::builder().max_objects(0).build(DEFINE_GLOBAL).err(),
+            Some(Error::OutOfMemory)
+        );
+    }
+
+    #[test]
+    fn fail_to_build_with_huge_heap() {
+        for count in [usize::MAX, usize::MAX / rib::FIELD_COUNT, usize::MAX / 8] {
+            assert_eq!(
+                Vm::builder().max_objects(count).build(DEFINE_GLOBAL).err(),
+                Some(Error::OutOfMemory)
+            );
+        }
+    }
+
+    #[test]
+    fn build_with_stack() {
+        // (set! x (get 1)) ((rib id 0 1) x)
+        let input = encode_program(
+            &["x"],
+            vec![
+                Code::Get(Operand::Stack(1)),
+                Code::Set(Operand::Symbol(4)),
+                Code::Constant(Constant::Number(Primitive::Id as u64)),
+                Code::Constant(Constant::Number(0)),
+                Code::Constant(Constant::Number(1)),
+                Code::Constant(Constant::Number(3)),
+                Code::Apply(Operand::Symbol(0)),
+                Code::Get(Operand::Symbol(4)),
+                Code::Constant(Constant::Number(1)),
+                Code::Apply(Operand::Stack(2)),
+            ],
+        );
+        let mut vm = Vm::builder()
+            .stack([1, -2])
+            .build(input.as_bytes())
+            .unwrap();
+
+        assert_eq!(
+            vm.stack().collect::<Vec<_>>(),
+            [Object::Number(-2i64 as u64), Object::Number(1)]
+        );
+        assert_eq!(vm.validate(), []);
+
+        vm.run().unwrap();
+
+        assert_eq!(vm.global("x"), Some(Object::Number(1)));
+    }
+
+    #[test]
+    fn fail_to_decode_with_too_small_heap() {
+        assert_eq!(
+            Vm::builder().max_objects(1 << 12).build(REPL).err(),
+            Some(Error::OutOfMemory)
+        );
+    }
+
+    #[test]
+    fn fail_to_allocate_live_ribs() {
+        let mut vm = Vm::builder()
+            .max_objects(1 << 8)
+            .build(DEFINE_GLOBAL)
+            .unwrap();
+
+        for _ in 0..vm.space_size {
+            if let Err(error) = vm.push(ZERO, PAIR_TAG) {
+                assert_eq!(error, Error::OutOfMemory);
+                return;
+            }
+        }
+
+        unreachable!();
+    }
+
+    #[test]
+    fn allocate_after_running_out_of_memory() {
+        let mut vm = Vm::builder()
+            .max_objects(1 << 8)
+            .build(DEFINE_GLOBAL)
+            .unwrap();
+        let mut count = 0;
+
+        while vm.push(ZERO, PAIR_TAG).is_ok() {
+            count += 1;
+        }
+
+        assert_eq!(vm.intern("foo"), Err(Error::OutOfMemory));
+        assert_eq!(vm.push(ZERO, PAIR_TAG), Err(Error::OutOfMemory));
+        assert!(vm.allocation_index <= vm.allocation_limit);
+
+        for _ in 0..count {
+            vm.pop();
+        }
+
+        vm.intern("foo").unwrap();
+        vm.run().unwrap();
+
+        assert_eq!(vm.get_tag(vm.global("id").unwrap()), CLOSURE_TAG);
+    }
+
+    #[test]
+    fn add_symbols() {
+        let mut vm = Vm::new(DEFINE_GLOBAL);
+        let mut symbol_vm = Vm::builder()
+            .symbols(["foo", "bar"])
+            .build(DEFINE_GLOBAL)
+            .unwrap();
+
+        assert_eq!(
+            symbol_vm.get_list_length(symbol_vm.symbol_table).to_raw(),
+            vm.get_list_length(vm.symbol_table).to_raw() + 2
+        );
+
+        symbol_vm.run().unwrap();
+    }
+
+    #[test]
+    fn collect_garbages_in_repl() {
+        let input = "(- (* 6 7) (+ 1 2))\n".repeat(10);
+        let collections = Cell::new(0);
+        let mut output = vec![];
+
+        Vm::builder()
+            .max_objects(6500)
+            .reader(input.as_bytes())
+            .writer(&mut output)
+            .tracer(CollectionTracer(&collections))
+            .build(REPL)
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert!(collections.get() > 10);
+        assert_eq!(
+            output,
+            ["> 39\n".repeat(10), "> ".into()].concat().as_bytes()
+        );
     }
 }
@@ -0,0 +1,179 @@
+use super::{Vm, CLOSURE_TAG, PAIR_TAG};
+use crate::{error::Error, frame::Frame, object::Object};
+
+impl Vm<'_> {
+    /// Returns call frames of a current continuation from the innermost one.
+    ///
+    /// Frames of tail calls are not present as they are replaced by callees.
+    pub fn backtrace(&self) -> Vec<Frame> {
+        let mut frames = vec![];
+        let mut stack = self.stack;
+
+        loop {
+            while stack.is_rib() && self.get_tag(stack) == PAIR_TAG {
+                stack = self.get_cdr(stack);
+            }
+
+            if !stack.is_rib() {
+                break;
+            }
+
+            let procedure = self.get_cdr(stack);
+
+            // The frame of a program has no procedure.
+            if !procedure.is_rib() || self.get_tag(procedure) != CLOSURE_TAG {
+                break;
+            }
+
+            frames.push(Frame {
+                names: self.get_global_names(procedure),
+            });
+            stack = self.get_car(stack);
+        }
+
+        frames
+    }
+
+    // Only errors raised in procedures are wrapped. Resource and I/O errors are
+    // not caused by the procedures and returned as they are.
+    pub(super) fn with_backtrace(&self, error: Error) -> Error {
+        if matches!(error, Error::OutOfMemory | Error::Io(_)) {
+            return error;
+        }
+
+        let backtrace = self.backtrace();
+
+        if backtrace.is_empty() {
+            error
+        } else {
+            Error::Runtime {
+                error: error.into(),
+                backtrace,
+            }
+        }
+    }
+
+    fn get_global_names(&self, procedure: Object) -> Vec<String> {
+        self.symbols(self.find_symbol_table())
+            .filter(|&symbol| self.get_car(symbol) == procedure)
+            .flat_map(|symbol| self.get_symbol_name(symbol))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        code::{Code, Constant, Operand, Procedure},
+        encoder::encode_program,
+        vm::REPL,
+    };
+    use std::io;
+
+    // (define (g x) (g))
+    // (define h g)
+    // (define (f) (g 42) (g 42))
+    // (f)
+    fn encode_nested_call() -> String {
+        let call_g = || {
+            vec![
+                Code::Constant(Constant::Number(42)),
+                Code::Constant(Constant::Number(1)),
+                Code::Apply(Operand::Symbol(5)),
+            ]
+        };
+
+        encode_program(
+            &["f", "g", "h"],
+            vec![
+                Code::Constant(Constant::Procedure(Procedure::new(
+                    1,
+                    false,
+                    vec![
+                        Code::Constant(Constant::Number(0)),
+                        Code::Apply(Operand::Symbol(5)),
+                    ],
+                ))),
+                Code::Set(Operand::Symbol(5)),
+                Code::Get(Operand::Symbol(5)),
+                Code::Set(Operand::Symbol(6)),
+                Code::Constant(Constant::Procedure(Procedure::new(
+                    0,
+                    false,
+                    [call_g(), call_g()].concat(),
+                ))),
+                Code::Set(Operand::Symbol(4)),
+                Code::Constant(Constant::Number(0)),
+                Code::Apply(Operand::Symbol(4)),
+            ],
+        )
+    }
+
+    #[test]
+    fn backtrace_program() {
+        let input = encode_nested_call();
+        let vm = Vm::new(input.as_bytes());
+
+        assert_eq!(vm.backtrace(), []);
+    }
+
+    #[test]
+    fn backtrace_procedure_defined_in_repl() {
+        let mut vm = Vm::builder()
+            .reader(b"(define f (lambda (x) (x)))\n(f 1)\n".as_slice())
+            .writer(io::sink())
+            .build(REPL)
+            .unwrap();
+
+        let Err(Error::Runtime { backtrace, .. }) = vm.run() else {
+            panic!("runtime error expected");
+        };
+
+        assert_eq!(
+            backtrace.first(),
+            Some(&Frame {
+                names: vec!["f".into()]
+            })
+        );
+    }
+
+    #[test]
+    fn do_not_wrap_out_of_memory() {
+        let mut vm = Vm::builder()
+            .max_objects(1 << 16)
+            .reader(b"(define f (lambda (x) (+ (f x) 1)))\n(f 1)\n".as_slice())
+            .writer(io::sink())
+            .build(REPL)
+            .unwrap();
+
+        assert_eq!(vm.run(), Err(Error::OutOfMemory));
+    }
+
+    #[test]
+    fn backtrace_runtime_error() {
+        let input = encode_nested_call();
+        let mut vm = Vm::new(input.as_bytes());
+
+        assert_eq!(
+            vm.run(),
+            Err(Error::Runtime {
+                error: Error::ArgumentCount {
+                    procedure: Some("g".into()),
+                    expected: 1,
+                    variadic: false,
+                    actual: 0,
+                }
+                .into(),
+                backtrace: vec![
+                    Frame {
+                        names: vec!["g".into(), "h".into()]
+                    },
+                    Frame {
+                        names: vec!["f".into()]
+                    },
+                ],
+            })
+        );
+    }
+}
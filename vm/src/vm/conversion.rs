@@ -0,0 +1,310 @@
+use super::{Vm, PAIR_TAG, STRING_TAG};
+use crate::{error::Error, object::Object};
+
+/// A value convertible into an object in a virtual machine.
+pub trait IntoObject {
+    fn into_object(self, vm: &mut Vm<'_>) -> Result<Object, Error>;
+}
+
+/// A value convertible from an object in a virtual machine.
+pub trait FromObject: Sized {
+    fn from_object(vm: &Vm<'_>, object: Object) -> Result<Self, Error>;
+}
+
+/// A symbol name.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Symbol(pub String);
+
+impl IntoObject for Object {
+    fn into_object(self, _: &mut Vm<'_>) -> Result<Object, Error> {
+        Ok(self)
+    }
+}
+
+impl FromObject for Object {
+    fn from_object(_: &Vm<'_>, object: Object) -> Result<Self, Error> {
+        Ok(object)
+    }
+}
+
+macro_rules! impl_integer {
+    ($($type:ty),*) => {
+        $(
+            impl IntoObject for $type {
+                fn into_object(self, _: &mut Vm<'_>) -> Result<Object, Error> {
+                    i64::try_from(self)
+                        .map(|number| Object::Number(number as u64))
+                        .map_err(|_| Error::IllegalConversion(Object::Number(self as u64)))
+                }
+            }
+
+            impl FromObject for $type {
+                fn from_object(_: &Vm<'_>, object: Object) -> Result<Self, Error> {
+                    match object {
+                        Object::Number(number) => {
+                            (number as i64).try_into().map_err(|_| Error::IllegalConversion(object))
+                        }
+                        Object::Rib(_) => Err(Error::IllegalConversion(object)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl IntoObject for bool {
+    fn into_object(self, vm: &mut Vm<'_>) -> Result<Object, Error> {
+        Ok(vm.get_boolean(self))
+    }
+}
+
+impl FromObject for bool {
+    fn from_object(vm: &Vm<'_>, object: Object) -> Result<Self, Error> {
+        if object == vm.get_true() {
+            Ok(true)
+        } else if object == vm.r#false {
+            Ok(false)
+        } else {
+            Err(Error::IllegalConversion(object))
+        }
+    }
+}
+
+impl IntoObject for () {
+    fn into_object(self, vm: &mut Vm<'_>) -> Result<Object, Error> {
+        Ok(vm.get_nil())
+    }
+}
+
+impl FromObject for () {
+    fn from_object(vm: &Vm<'_>, object: Object) -> Result<Self, Error> {
+        if object == vm.get_nil() {
+            Ok(())
+        } else {
+            Err(Error::IllegalConversion(object))
+        }
+    }
+}
+
+impl IntoObject for &str {
+    fn into_object(self, vm: &mut Vm<'_>) -> Result<Object, Error> {
+        let mut list = vm.get_nil();
+        let mut length = 0;
+
+        for character in self.chars().rev() {
+            list = vm.allocate_rib(Object::Number(character as u64), list, PAIR_TAG)?;
+            length += 1;
+        }
+
+        vm.allocate_rib(list, Object::Number(length), STRING_TAG)
+    }
+}
+
+impl IntoObject for String {
+    fn into_object(self, vm: &mut Vm<'_>) -> Result<Object, Error> {
+        self.as_str().into_object(vm)
+    }
+}
+
+impl FromObject for String {
+    fn from_object(vm: &Vm<'_>, object: Object) -> Result<Self, Error> {
+        if !object.is_rib() || vm.get_tag(object) != STRING_TAG {
+            return Err(Error::IllegalConversion(object));
+        }
+
+        let mut string = String::new();
+        let mut list = vm.get_car(object);
+
+        while list != vm.get_nil() {
+            if !list.is_rib() || vm.get_tag(list) != PAIR_TAG {
+                return Err(Error::IllegalConversion(object));
+            }
+
+            let character = vm.get_car(list);
+            let Object::Number(code) = character else {
+                return Err(Error::IllegalConversion(character));
+            };
+
+            string.push(
+                u32::try_from(code)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or(Error::IllegalConversion(character))?,
+            );
+            list = vm.get_cdr(list);
+        }
+
+        Ok(string)
+    }
+}
+
+impl IntoObject for Symbol {
+    fn into_object(self, vm: &mut Vm<'_>) -> Result<Object, Error> {
+        vm.intern(&self.0)
+    }
+}
+
+impl FromObject for Symbol {
+    fn from_object(vm: &Vm<'_>, object: Object) -> Result<Self, Error> {
+        vm.get_symbol_name(object)
+            .map(Symbol)
+            .ok_or(Error::IllegalConversion(object))
+    }
+}
+
+impl<T: IntoObject> IntoObject for Vec<T> {
+    fn into_object(self, vm: &mut Vm<'_>) -> Result<Object, Error> {
+        let root = vm.host_roots.len();
+
+        // Keep a list in roots as conversion of elements can move it.
+        vm.host_roots.push(vm.get_nil());
+
+        let result = into_list(vm, self, root);
+
+        vm.host_roots.truncate(root);
+
+        result
+    }
+}
+
+fn into_list<T: IntoObject>(vm: &mut Vm<'_>, values: Vec<T>, root: usize) -> Result<Object, Error> {
+    for value in values.into_iter().rev() {
+        let car = value.into_object(vm)?;
+        vm.host_roots[root] = vm.allocate_rib(car, vm.host_roots[root], PAIR_TAG)?;
+    }
+
+    Ok(vm.host_roots[root])
+}
+
+impl<T: FromObject> FromObject for Vec<T> {
+    fn from_object(vm: &Vm<'_>, object: Object) -> Result<Self, Error> {
+        let mut values = vec![];
+        let mut list = object;
+
+        while list != vm.get_nil() {
+            if !list.is_rib() || vm.get_tag(list) != PAIR_TAG {
+                return Err(Error::IllegalConversion(object));
+            }
+
+            values.push(T::from_object(vm, vm.get_car(list))?);
+            list = vm.get_cdr(list);
+        }
+
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // spell-checker: disable-next-line
+    const INPUT: &[u8] = b"#oof,,,,bir;#y";
+
+    fn round_trip<T: IntoObject + FromObject>(vm: &mut Vm, value: T) -> Result<T, Error> {
+        let object = value.into_object(vm)?;
+
+        T::from_object(vm, object)
+    }
+
+    #[test]
+    fn convert_integers() {
+        let mut vm = Vm::new(INPUT);
+
+        assert_eq!(round_trip(&mut vm, 42i64), Ok(42));
+        assert_eq!(round_trip(&mut vm, -42i32), Ok(-42));
+        assert_eq!(round_trip(&mut vm, u8::MAX), Ok(u8::MAX));
+        assert_eq!(
+            round_trip(&mut vm, u64::MAX),
+            Err(Error::IllegalConversion(Object::Number(u64::MAX)))
+        );
+        assert_eq!(
+            u8::from_object(&vm, Object::Number(256)),
+            Err(Error::IllegalConversion(Object::Number(256)))
+        );
+        assert_eq!(
+            u32::from_object(&vm, Object::Number(-1i64 as u64)),
+            Err(Error::IllegalConversion(Object::Number(-1i64 as u64)))
+        );
+    }
+
+    #[test]
+    fn convert_booleans() {
+        let mut vm = Vm::new(INPUT);
+
+        assert_eq!(round_trip(&mut vm, true), Ok(true));
+        assert_eq!(round_trip(&mut vm, false), Ok(false));
+        assert_eq!(
+            bool::from_object(&vm, Object::Number(0)),
+            Err(Error::IllegalConversion(Object::Number(0)))
+        );
+    }
+
+    #[test]
+    fn convert_nil() {
+        let mut vm = Vm::new(INPUT);
+
+        assert_eq!(round_trip(&mut vm, ()), Ok(()));
+        assert_eq!(
+            <()>::from_object(&vm, vm.r#false),
+            Err(Error::IllegalConversion(vm.r#false))
+        );
+    }
+
+    #[test]
+    fn convert_strings() {
+        let mut vm = Vm::new(INPUT);
+
+        assert_eq!(round_trip(&mut vm, String::new()), Ok("".into()));
+        assert_eq!(
+            round_trip(&mut vm, "hello, λ".to_owned()),
+            Ok("hello, λ".into())
+        );
+        assert_eq!(
+            String::from_object(&vm, Object::Number(0)),
+            Err(Error::IllegalConversion(Object::Number(0)))
+        );
+    }
+
+    #[test]
+    fn convert_symbols() {
+        let mut vm = Vm::new(INPUT);
+
+        assert_eq!(
+            round_trip(&mut vm, Symbol("foo".into())),
+            Ok(Symbol("foo".into()))
+        );
+        assert_eq!(
+            round_trip(&mut vm, Symbol("bar".into())),
+            Ok(Symbol("bar".into()))
+        );
+    }
+
+    #[test]
+    fn convert_lists() {
+        let mut vm = Vm::new(INPUT);
+
+        assert_eq!(round_trip(&mut vm, Vec::<i64>::new()), Ok(vec![]));
+        assert_eq!(round_trip(&mut vm, vec![1, 2, 3]), Ok(vec![1, 2, 3]));
+        assert_eq!(
+            round_trip(&mut vm, vec![vec![true], vec![false, true]]),
+            Ok(vec![vec![true], vec![false, true]])
+        );
+        assert_eq!(
+            Vec::<i64>::from_object(&vm, vm.r#false),
+            Err(Error::IllegalConversion(vm.r#false))
+        );
+    }
+
+    #[test]
+    fn convert_list_with_garbage_collection() {
+        let mut vm = Vm::builder().max_objects(1 << 10).build(INPUT).unwrap();
+        let strings = (0..100).map(|index| index.to_string()).collect::<Vec<_>>();
+
+        for _ in 0..10 {
+            assert_eq!(round_trip(&mut vm, strings.clone()), Ok(strings.clone()));
+        }
+    }
+}
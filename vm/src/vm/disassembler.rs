@@ -0,0 +1,189 @@
+use super::{Vm, CLOSURE_TAG, SYMBOL_TAG, ZERO};
+use crate::{instruction::Instruction, name::display_name, object::Object};
+use std::fmt::{self, Write};
+
+const INDENT: &str = "  ";
+
+impl Vm<'_> {
+    /// Disassembles instructions from a current program counter.
+    ///
+    /// Each line contains an instruction and its operand. Stack operands are
+    /// printed as indices and symbol operands as their names. Bodies of
+    /// procedure constants and `then` branches of `if` instructions are
+    /// indented while `else` branches follow `if` instructions.
+    pub fn disassemble(&self) -> String {
+        let mut output = String::new();
+
+        // Writing to a string never fails.
+        self.disassemble_codes(&mut output, self.program_counter, 0)
+            .unwrap();
+
+        output
+    }
+
+    fn disassemble_codes(
+        &self,
+        output: &mut impl Write,
+        mut code: Object,
+        depth: usize,
+    ) -> fmt::Result {
+        while code.is_rib() {
+            let operand = self.get_cdr(code);
+            let next = self.get_tag(code);
+
+            for _ in 0..depth {
+                output.write_str(INDENT)?;
+            }
+
+            match self.get_car(code).to_raw() {
+                Instruction::APPLY => {
+                    write!(output, "{} ", if next == ZERO { "jump" } else { "call" })?;
+                    self.disassemble_operand(output, operand)?;
+                }
+                Instruction::SET => {
+                    write!(output, "set ")?;
+                    self.disassemble_operand(output, operand)?;
+                }
+                Instruction::GET => {
+                    write!(output, "get ")?;
+                    self.disassemble_operand(output, operand)?;
+                }
+                Instruction::CONSTANT => {
+                    write!(output, "constant ")?;
+                    self.disassemble_constant(output, operand, depth)?;
+                }
+                Instruction::IF => {
+                    writeln!(output, "if")?;
+                    self.disassemble_codes(output, operand, depth + 1)?;
+                    code = next;
+                    continue;
+                }
+                Instruction::HALT => writeln!(output, "halt")?,
+                instruction => {
+                    writeln!(output, "unknown {instruction}")?;
+                    break;
+                }
+            }
+
+            code = next;
+        }
+
+        Ok(())
+    }
+
+    fn disassemble_operand(&self, output: &mut impl Write, operand: Object) -> fmt::Result {
+        if operand.is_rib() {
+            self.disassemble_symbol(output, operand)
+        } else {
+            writeln!(output, "{}", operand.to_raw())
+        }
+    }
+
+    fn disassemble_constant(
+        &self,
+        output: &mut impl Write,
+        constant: Object,
+        depth: usize,
+    ) -> fmt::Result {
+        if !constant.is_rib() {
+            writeln!(output, "{}", constant.to_raw() as i64)
+        } else if self.get_tag(constant) == SYMBOL_TAG {
+            write!(output, "'")?;
+            self.disassemble_symbol(output, constant)
+        } else if self.get_tag(constant) == CLOSURE_TAG {
+            let code = self.get_car(constant);
+            let parameter_info = self.get_car(code).to_raw();
+
+            write!(output, "procedure {}", parameter_info >> 1)?;
+
+            if parameter_info & 1 != 0 {
+                write!(output, " variadic")?;
+            }
+
+            writeln!(output)?;
+            self.disassemble_codes(output, self.get_tag(code), depth + 1)
+        } else {
+            writeln!(output, "rib")
+        }
+    }
+
+    fn disassemble_symbol(&self, output: &mut impl Write, symbol: Object) -> fmt::Result {
+        match self.get_symbol_name(symbol) {
+            Some(name) => writeln!(output, "{}", display_name(&name)),
+            None => writeln!(output, "rib"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        code::{Code, Constant, Operand, Procedure},
+        encoder::encode_program,
+    };
+
+    fn disassemble(symbols: &[&str], body: Vec<Code>) -> String {
+        let input = encode_program(symbols, body);
+
+        let vm = Vm::new(input.as_bytes());
+
+        vm.disassemble()
+    }
+
+    #[test]
+    fn disassemble_codes() {
+        assert_eq!(
+            disassemble(
+                &["x"],
+                vec![
+                    Code::Constant(Constant::Number(42)),
+                    Code::Set(Operand::Symbol(4)),
+                    Code::Get(Operand::Symbol(4)),
+                    Code::Constant(Constant::Symbol(4)),
+                    Code::Apply(Operand::Symbol(0)),
+                    Code::Apply(Operand::Stack(1)),
+                ]
+            ),
+            "constant 42\nset x\nget x\nconstant 'x\ncall rib\njump 1\n"
+        );
+    }
+
+    #[test]
+    fn disassemble_procedure() {
+        assert_eq!(
+            disassemble(
+                &[],
+                vec![
+                    Code::Constant(Constant::Procedure(Procedure::new(
+                        1,
+                        true,
+                        vec![
+                            Code::Get(Operand::Stack(0)),
+                            Code::Apply(Operand::Symbol(0))
+                        ]
+                    ))),
+                    Code::Apply(Operand::Symbol(0)),
+                ]
+            ),
+            "constant procedure 1 variadic\n  get 0\n  jump rib\njump rib\n"
+        );
+    }
+
+    #[test]
+    fn disassemble_if() {
+        assert_eq!(
+            disassemble(
+                &[],
+                vec![
+                    Code::Get(Operand::Symbol(1)),
+                    Code::If(
+                        vec![Code::Apply(Operand::Symbol(0))],
+                        vec![Code::Apply(Operand::Symbol(2))]
+                    ),
+                ]
+            ),
+            "get ||\nif\n  jump rib\njump ||\n"
+        );
+    }
+}
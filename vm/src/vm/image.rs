@@ -0,0 +1,333 @@
+use super::{Vm, HEAP_BOTTOM, ZERO};
+use crate::{error::Error, object::Object, rib};
+use std::io::{self, ErrorKind, Read, Write};
+
+const MAGIC: &[u8; 4] = b"RVMI";
+const VERSION: u32 = 1;
+
+const NUMBER_KIND: u8 = 0;
+const RIB_KIND: u8 = 1;
+
+impl Vm<'_> {
+    /// Saves a heap image.
+    ///
+    /// An image contains a current semi-space of a heap, roots and an allocation
+    /// state. Primitives registered by a host are not saved.
+    pub fn save_image(&self, image: &mut impl Write) -> Result<(), Error> {
+        self.write_image(image).map_err(Error::from)
+    }
+
+    fn write_image(&self, image: &mut impl Write) -> io::Result<()> {
+        image.write_all(MAGIC)?;
+        image.write_all(&VERSION.to_le_bytes())?;
+
+        for size in [
+            self.space_size,
+            self.allocation_limit,
+            self.allocation_index,
+        ] {
+            image.write_all(&(size as u64).to_le_bytes())?;
+        }
+
+        for object in self.roots() {
+            write_object(image, object)?;
+        }
+
+        for &object in &self.heap[self.allocation_limit - self.space_size..self.allocation_index] {
+            write_object(image, object)?;
+        }
+
+        image.flush()
+    }
+
+    pub(super) fn read_image(&mut self, image: &mut impl Read) -> Result<(), Error> {
+        let mut magic = [0; MAGIC.len()];
+        read_bytes(image, &mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(Error::MalformedImage);
+        }
+
+        let mut version = [0; 4];
+        read_bytes(image, &mut version)?;
+        let version = u32::from_le_bytes(version);
+
+        if version != VERSION {
+            return Err(Error::UnsupportedImageVersion(version));
+        }
+
+        let space_size = read_size(image)?;
+        let allocation_limit = read_size(image)?;
+        let allocation_index = read_size(image)?;
+        let heap_size = space_size.checked_mul(2).ok_or(Error::MalformedImage)?;
+
+        if space_size == 0
+            || !space_size.is_multiple_of(rib::FIELD_COUNT)
+            || ![HEAP_BOTTOM + space_size, HEAP_BOTTOM + heap_size].contains(&allocation_limit)
+            || !(allocation_limit - space_size..=allocation_limit).contains(&allocation_index)
+            || !(allocation_index - HEAP_BOTTOM).is_multiple_of(rib::FIELD_COUNT)
+        {
+            return Err(Error::MalformedImage);
+        } else if space_size > self.space_size {
+            return Err(Error::OutOfMemory);
+        }
+
+        let start = allocation_limit - space_size;
+        let is_valid = |object| match object {
+            Object::Number(_) => true,
+            Object::Rib(index) => {
+                let index = index as usize;
+                (start..allocation_index).contains(&index)
+                    && (index - HEAP_BOTTOM).is_multiple_of(rib::FIELD_COUNT)
+            }
+        };
+
+        let mut roots = [ZERO; 4];
+
+        for root in &mut roots {
+            *root = read_object(image)?;
+        }
+
+        let mut heap = vec![ZERO; heap_size];
+
+        for object in &mut heap[start..allocation_index] {
+            *object = read_object(image)?;
+        }
+
+        if !roots.into_iter().chain(heap.iter().copied()).all(is_valid) {
+            return Err(Error::MalformedImage);
+        }
+
+        [
+            self.stack,
+            self.program_counter,
+            self.r#false,
+            self.symbol_table,
+        ] = roots;
+        self.heap = heap;
+        self.space_size = space_size;
+        self.allocation_limit = allocation_limit;
+        self.allocation_index = allocation_index;
+
+        Ok(())
+    }
+
+    fn roots(&self) -> [Object; 4] {
+        [
+            self.stack,
+            self.program_counter,
+            self.r#false,
+            self.symbol_table,
+        ]
+    }
+}
+
+fn write_object(image: &mut impl Write, object: Object) -> io::Result<()> {
+    image.write_all(&[match object {
+        Object::Number(_) => NUMBER_KIND,
+        Object::Rib(_) => RIB_KIND,
+    }])?;
+    image.write_all(&object.to_raw().to_le_bytes())
+}
+
+fn read_object(image: &mut impl Read) -> Result<Object, Error> {
+    let mut kind = [0];
+    let mut value = [0; 8];
+
+    read_bytes(image, &mut kind)?;
+    read_bytes(image, &mut value)?;
+
+    let value = u64::from_le_bytes(value);
+
+    match kind[0] {
+        NUMBER_KIND => Ok(Object::Number(value)),
+        RIB_KIND => Ok(Object::Rib(value)),
+        _ => Err(Error::MalformedImage),
+    }
+}
+
+fn read_size(image: &mut impl Read) -> Result<usize, Error> {
+    let mut bytes = [0; 8];
+    read_bytes(image, &mut bytes)?;
+
+    u64::from_le_bytes(bytes)
+        .try_into()
+        .map_err(|_| Error::MalformedImage)
+}
+
+fn read_bytes(image: &mut impl Read, bytes: &mut [u8]) -> Result<(), Error> {
+    image.read_exact(bytes).map_err(|error| match error.kind() {
+        ErrorKind::UnexpectedEof => Error::MalformedImage,
+        _ => error.into(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{instruction::Instruction, profiler::Profiler, status::Status, vm::REPL};
+
+    fn save_image(vm: &Vm) -> Vec<u8> {
+        let mut image = vec![];
+        vm.save_image(&mut image).unwrap();
+        image
+    }
+
+    #[test]
+    fn save_and_load_image() {
+        let vm = Vm::new(REPL);
+        let image = save_image(&vm);
+        let other = Vm::load_image(&mut image.as_slice()).unwrap();
+
+        assert_eq!(other.roots(), vm.roots());
+        assert_eq!(save_image(&other), image);
+    }
+
+    #[test]
+    fn run_repl_from_image() {
+        let image = save_image(&Vm::new(REPL));
+        let mut output = vec![];
+
+        Vm::builder()
+            .reader(b"(+ 1 2)\n".as_slice())
+            .writer(&mut output)
+            .build_image(&mut image.as_slice())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(output, b"> 3\n> ");
+    }
+
+    #[test]
+    fn resume_from_image() {
+        let mut vm = Vm::builder()
+            .reader(b"".as_slice())
+            .writer(vec![])
+            .build(REPL)
+            .unwrap();
+
+        assert_eq!(vm.run_with_fuel(100), Ok(Status::OutOfFuel));
+
+        let image = save_image(&vm);
+        let mut other = Vm::builder()
+            .reader(b"".as_slice())
+            .writer(vec![])
+            .build_image(&mut image.as_slice())
+            .unwrap();
+
+        vm.run().unwrap();
+        other.run().unwrap();
+
+        assert_eq!(save_image(&other), save_image(&vm));
+    }
+
+    #[test]
+    fn run_repl_from_image_after_prelude() {
+        let mut vm = Vm::new(REPL);
+
+        assert_eq!(vm.run_until_io(), Ok(Status::Continue));
+
+        let image = save_image(&vm);
+        let mut output = vec![];
+        let mut profiler = Profiler::new();
+
+        Vm::builder()
+            .reader(b"(+ 1 2)\n".as_slice())
+            .writer(&mut output)
+            .tracer(&mut profiler)
+            .build_image(&mut image.as_slice())
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert_eq!(output, b"> 3\n> ");
+
+        let mut other_profiler = Profiler::new();
+
+        Vm::builder()
+            .reader(b"(+ 1 2)\n".as_slice())
+            .writer(vec![])
+            .tracer(&mut other_profiler)
+            .build(REPL)
+            .unwrap()
+            .run()
+            .unwrap();
+
+        assert!(
+            profiler.instruction_count(Instruction::Apply)
+                < other_profiler.instruction_count(Instruction::Apply)
+        );
+    }
+
+    #[test]
+    fn fail_to_load_image_larger_than_heap() {
+        let image = save_image(&Vm::new(REPL));
+
+        assert_eq!(
+            Vm::builder()
+                .max_objects(1 << 13)
+                .build_image(&mut image.as_slice())
+                .err(),
+            Some(Error::OutOfMemory)
+        );
+    }
+
+    #[test]
+    fn fail_to_load_image_with_huge_heap() {
+        let size = 3usize << 40;
+        let image = [
+            MAGIC.as_slice(),
+            &VERSION.to_le_bytes(),
+            &(size as u64).to_le_bytes(),
+            &(size as u64).to_le_bytes(),
+            &0u64.to_le_bytes(),
+        ]
+        .concat();
+
+        assert_eq!(
+            Vm::load_image(&mut image.as_slice()).err(),
+            Some(Error::OutOfMemory)
+        );
+    }
+
+    #[test]
+    fn fail_to_load_image_with_wrong_magic() {
+        assert_eq!(
+            Vm::load_image(&mut b"RVMX".as_slice()).err(),
+            Some(Error::MalformedImage)
+        );
+    }
+
+    #[test]
+    fn fail_to_load_image_with_unsupported_version() {
+        assert_eq!(
+            Vm::load_image(&mut [MAGIC.as_slice(), &2u32.to_le_bytes()].concat().as_slice()).err(),
+            Some(Error::UnsupportedImageVersion(2))
+        );
+    }
+
+    #[test]
+    fn fail_to_load_truncated_image() {
+        let image = save_image(&Vm::new(REPL));
+
+        assert_eq!(
+            Vm::load_image(&mut &image[..image.len() - 1]).err(),
+            Some(Error::MalformedImage)
+        );
+    }
+
+    #[test]
+    fn fail_to_load_image_with_dangling_rib() {
+        let mut image = save_image(&Vm::new(REPL));
+        let length = image.len();
+
+        image[length - 9] = RIB_KIND;
+        image[length - 8..].copy_from_slice(&u64::MAX.to_le_bytes());
+
+        assert_eq!(
+            Vm::load_image(&mut image.as_slice()).err(),
+            Some(Error::MalformedImage)
+        );
+    }
+}
@@ -0,0 +1,574 @@
+use super::{Vm, CLOSURE_TAG, SYMBOL_TAG, ZERO};
+use crate::{
+    diagnostic::{Diagnostic, DiagnosticKind},
+    instruction::Instruction,
+    object::Object,
+    primitive::Primitive,
+};
+use num_traits::FromPrimitive;
+use std::collections::HashSet;
+
+impl Vm<'_> {
+    /// Validates instructions from a current program counter.
+    ///
+    /// Diagnostics refer to lines in an output of [`Vm::disassemble`]. Stack
+    /// operands are checked against stack depths known statically and
+    /// environments that closures can capture. Procedures called by symbols
+    /// must be bound to closures or assigned somewhere in a program while ones
+    /// called by stack operands must not be known as non-procedures.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut assigned = HashSet::new();
+        self.collect_assignments(self.program_counter, &mut assigned, &mut HashSet::new());
+
+        let mut validator = Validator {
+            vm: self,
+            assigned,
+            path: HashSet::new(),
+            line: 0,
+            diagnostics: vec![],
+        };
+
+        // The stack of a program contains initial objects on a frame of a `halt`
+        // instruction.
+        let mut stack = self
+            .stack()
+            .map(|object| validator.classify(object))
+            .collect::<Vec<_>>();
+        stack.reverse();
+
+        validator.validate_codes(self.program_counter, Some(stack), Some(1));
+
+        validator.diagnostics
+    }
+
+    fn collect_assignments(
+        &self,
+        mut code: Object,
+        assigned: &mut HashSet<u64>,
+        visited: &mut HashSet<u64>,
+    ) {
+        while code.is_rib() && visited.insert(code.to_raw()) {
+            let operand = self.get_cdr(code);
+
+            match self.get_car(code).to_raw() {
+                Instruction::SET if operand.is_rib() => {
+                    assigned.insert(operand.to_raw());
+                }
+                Instruction::CONSTANT
+                    if operand.is_rib() && self.get_tag(operand) == CLOSURE_TAG =>
+                {
+                    let code = self.get_car(operand);
+
+                    if code.is_rib() {
+                        self.collect_assignments(self.get_tag(code), assigned, visited);
+                    }
+                }
+                Instruction::IF => self.collect_assignments(operand, assigned, visited),
+                _ => {}
+            }
+
+            code = self.get_tag(code);
+        }
+    }
+}
+
+// An object on a stack known statically.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Value {
+    Unknown,
+    Number(u64),
+    Procedure,
+    Rib,
+}
+
+struct Validator<'a, 'b> {
+    vm: &'a Vm<'b>,
+    assigned: HashSet<u64>,
+    path: HashSet<u64>,
+    line: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_, '_> {
+    // Instructions are traversed in the same order as a disassembler so that
+    // code shared by multiple branches is validated at each of its lines.
+    fn validate_codes(&mut self, code: Object, stack: Option<Vec<Value>>, extra: Option<usize>) {
+        let mut path = vec![];
+
+        self.validate_path(code, stack, extra, &mut path);
+
+        for code in path {
+            self.path.remove(&code);
+        }
+    }
+
+    // A stack contains objects pushed in a current procedure while an extra depth
+    // is a number of objects reachable beyond them through a frame and an
+    // environment. Either is `None` if it is unknown statically.
+    fn validate_path(
+        &mut self,
+        mut code: Object,
+        mut stack: Option<Vec<Value>>,
+        extra: Option<usize>,
+        path: &mut Vec<u64>,
+    ) {
+        let mut argument_count = None;
+
+        loop {
+            // Programs decoded from bytecodes are trees but heaps can be
+            // modified at runtime to have cycles.
+            if !self.path.insert(code.to_raw()) {
+                return;
+            }
+
+            path.push(code.to_raw());
+            self.line += 1;
+
+            let operand = self.vm.get_cdr(code);
+            let next = self.vm.get_tag(code);
+            let instruction = self.vm.get_car(code);
+
+            let Some(instruction) = (!instruction.is_rib())
+                .then(|| Instruction::from_u64(instruction.to_raw()))
+                .flatten()
+            else {
+                self.report(DiagnosticKind::IllegalInstruction(instruction.to_raw()));
+                return;
+            };
+
+            match instruction {
+                Instruction::Apply => {
+                    let result = if operand.is_rib() {
+                        self.validate_procedure_symbol(operand);
+                        self.apply_symbol(operand, stack.as_deref(), argument_count)
+                    } else {
+                        self.validate_stack_operand(operand, stack.as_deref(), extra);
+                        self.validate_stack_procedure(operand, stack.as_deref());
+                        Value::Unknown
+                    };
+
+                    // Pop arguments and an argument count and push a result. Built-in
+                    // primitives ignore argument counts and can consume fewer objects.
+                    stack = stack.zip(argument_count).and_then(|(mut stack, count)| {
+                        stack.truncate(stack.len().checked_sub(count + 1)?);
+                        stack.push(result);
+                        Some(stack)
+                    });
+
+                    if next == ZERO {
+                        return;
+                    }
+                }
+                Instruction::Set => {
+                    stack = self.pop(stack);
+
+                    if !operand.is_rib() {
+                        self.validate_stack_operand(operand, stack.as_deref(), extra);
+                    }
+                }
+                Instruction::Get => {
+                    let value = if operand.is_rib() {
+                        Value::Unknown
+                    } else {
+                        self.validate_stack_operand(operand, stack.as_deref(), extra);
+                        self.get_stack_value(operand, stack.as_deref())
+                    };
+
+                    if let Some(stack) = &mut stack {
+                        stack.push(value);
+                    }
+                }
+                Instruction::Constant => {
+                    if operand.is_rib() && self.vm.get_tag(operand) == CLOSURE_TAG {
+                        self.validate_procedure(operand, stack.as_ref().map(Vec::len), extra);
+                    }
+
+                    let value = self.classify(operand);
+
+                    if let Some(stack) = &mut stack {
+                        stack.push(value);
+                    }
+                }
+                Instruction::If => {
+                    stack = self.pop(stack);
+
+                    if !operand.is_rib() {
+                        self.report(DiagnosticKind::UnterminatedCode);
+                    } else {
+                        self.validate_codes(operand, stack.clone(), extra);
+                    }
+                }
+                Instruction::Halt => return,
+            }
+
+            argument_count = match (instruction, operand) {
+                (Instruction::Constant, Object::Number(count)) => usize::try_from(count).ok(),
+                _ => None,
+            };
+
+            if !next.is_rib() {
+                self.report(DiagnosticKind::UnterminatedCode);
+                return;
+            }
+
+            code = next;
+        }
+    }
+
+    fn validate_procedure(
+        &mut self,
+        procedure: Object,
+        depth: Option<usize>,
+        extra: Option<usize>,
+    ) {
+        let code = self.vm.get_car(procedure);
+
+        if !code.is_rib() {
+            self.validate_primitive(code.to_raw());
+            return;
+        }
+
+        let parameter_info = self.vm.get_car(code).to_raw();
+
+        self.validate_codes(
+            self.vm.get_tag(code),
+            usize::try_from((parameter_info >> 1) + (parameter_info & 1))
+                .ok()
+                .map(|count| vec![Value::Unknown; count]),
+            // A frame and a closure are followed by an environment captured
+            // from a current stack if the closure is closed.
+            depth.zip(extra).map(|(depth, extra)| 2 + depth + extra),
+        );
+    }
+
+    fn validate_procedure_symbol(&mut self, symbol: Object) {
+        if self.vm.get_tag(symbol) != SYMBOL_TAG {
+            return;
+        }
+
+        let value = self.vm.get_car(symbol);
+
+        if value.is_rib() && self.vm.get_tag(value) == CLOSURE_TAG {
+            let code = self.vm.get_car(value);
+
+            if !code.is_rib() {
+                self.validate_primitive(code.to_raw());
+            }
+        } else if !self.assigned.contains(&symbol.to_raw()) {
+            let name = self.vm.get_symbol_name(symbol).unwrap_or_default();
+
+            self.report(if value == self.vm.r#false {
+                DiagnosticKind::UndefinedProcedure(name)
+            } else {
+                DiagnosticKind::NonProcedure(name)
+            });
+        }
+    }
+
+    fn validate_stack_procedure(&mut self, index: Object, stack: Option<&[Value]>) {
+        if let Value::Number(_) | Value::Rib = self.get_stack_value(index, stack) {
+            self.report(DiagnosticKind::NonProcedureOperand(index.to_raw()));
+        }
+    }
+
+    fn validate_primitive(&mut self, index: u64) {
+        if Primitive::try_from(index).is_err() && !self.vm.primitives.contains_key(&index) {
+            self.report(DiagnosticKind::IllegalPrimitive(index));
+        }
+    }
+
+    fn validate_stack_operand(
+        &mut self,
+        index: Object,
+        stack: Option<&[Value]>,
+        extra: Option<usize>,
+    ) {
+        if let (Some(stack), Some(extra)) = (stack, extra) {
+            if index.to_raw() >= (stack.len() + extra) as u64 {
+                self.report(DiagnosticKind::StackOperandOutOfBounds(index.to_raw()));
+            }
+        }
+    }
+
+    // Returns a result of a call to a procedure bound to a symbol. Only closures
+    // created by the `rib` primitive with constant tags are known.
+    fn apply_symbol(&self, symbol: Object, stack: Option<&[Value]>, count: Option<usize>) -> Value {
+        let value = self.vm.get_car(symbol);
+
+        if self.assigned.contains(&symbol.to_raw())
+            || !value.is_rib()
+            || self.vm.get_tag(value) != CLOSURE_TAG
+            || self.vm.get_car(value) != Object::Number(Primitive::Rib as u64)
+            || count != Some(3)
+        {
+            return Value::Unknown;
+        }
+
+        match stack.and_then(|stack| stack.iter().rev().nth(1)) {
+            Some(&Value::Number(tag)) if Object::Number(tag) == CLOSURE_TAG => Value::Procedure,
+            Some(Value::Number(_)) => Value::Rib,
+            _ => Value::Unknown,
+        }
+    }
+
+    fn get_stack_value(&self, index: Object, stack: Option<&[Value]>) -> Value {
+        stack
+            .and_then(|stack| stack.iter().rev().nth(index.to_raw() as usize))
+            .copied()
+            .unwrap_or(Value::Unknown)
+    }
+
+    fn classify(&self, object: Object) -> Value {
+        match object {
+            Object::Number(number) => Value::Number(number),
+            Object::Rib(_) if self.vm.get_tag(object) == CLOSURE_TAG => Value::Procedure,
+            Object::Rib(_) => Value::Rib,
+        }
+    }
+
+    fn pop(&mut self, stack: Option<Vec<Value>>) -> Option<Vec<Value>> {
+        let mut stack = stack?;
+
+        if stack.pop().is_none() {
+            self.report(DiagnosticKind::StackUnderflow);
+            return None;
+        }
+
+        Some(stack)
+    }
+
+    fn report(&mut self, kind: DiagnosticKind) {
+        self.diagnostics.push(Diagnostic {
+            line: self.line,
+            kind,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        code::{Code, Constant, Operand, Procedure},
+        encoder::encode_program,
+        vm::REPL,
+    };
+
+    // (f 42)
+    fn encode_call(codes: Vec<Code>) -> String {
+        encode_program(
+            &["f"],
+            [
+                codes,
+                vec![
+                    Code::Constant(Constant::Number(42)),
+                    Code::Constant(Constant::Number(1)),
+                    Code::Apply(Operand::Symbol(4)),
+                ],
+            ]
+            .concat(),
+        )
+    }
+
+    fn diagnostic(line: usize, kind: DiagnosticKind) -> Diagnostic {
+        Diagnostic { line, kind }
+    }
+
+    #[test]
+    fn validate_repl() {
+        assert_eq!(Vm::new(REPL).validate(), []);
+    }
+
+    #[test]
+    fn validate_assigned_procedure() {
+        let input = encode_call(vec![
+            Code::Constant(Constant::Procedure(Procedure::new(
+                1,
+                false,
+                vec![
+                    Code::Get(Operand::Stack(0)),
+                    Code::Apply(Operand::Symbol(0)),
+                ],
+            ))),
+            Code::Set(Operand::Symbol(4)),
+        ]);
+
+        assert_eq!(Vm::new(input.as_bytes()).validate(), []);
+    }
+
+    #[test]
+    fn validate_undefined_procedure() {
+        let input = encode_call(vec![]);
+
+        assert_eq!(
+            Vm::new(input.as_bytes()).validate(),
+            [diagnostic(
+                3,
+                DiagnosticKind::UndefinedProcedure("f".into())
+            )]
+        );
+    }
+
+    #[test]
+    fn validate_non_procedure() {
+        let input = encode_call(vec![]);
+        let mut vm = Vm::new(input.as_bytes());
+
+        vm.set_global("f", Object::Number(0)).unwrap();
+
+        assert_eq!(
+            vm.validate(),
+            [diagnostic(3, DiagnosticKind::NonProcedure("f".into()))]
+        );
+    }
+
+    #[test]
+    fn validate_primitive() {
+        let input = encode_call(vec![]);
+        let mut vm = Vm::new(input.as_bytes());
+
+        let closure = vm
+            .allocate_rib(Object::Number(100), ZERO, CLOSURE_TAG)
+            .unwrap();
+        vm.set_global("f", closure).unwrap();
+
+        assert_eq!(
+            vm.validate(),
+            [diagnostic(3, DiagnosticKind::IllegalPrimitive(100))]
+        );
+
+        vm.register_primitive(100, "f", 1, |arguments| arguments[0])
+            .unwrap();
+
+        assert_eq!(vm.validate(), []);
+    }
+
+    #[test]
+    fn validate_stack_operands() {
+        let input = encode_call(vec![
+            Code::Get(Operand::Stack(0)),
+            Code::Get(Operand::Stack(2)),
+            Code::Constant(Constant::Procedure(Procedure::new(
+                1,
+                false,
+                vec![
+                    Code::Get(Operand::Stack(4)),
+                    Code::Get(Operand::Stack(7)),
+                    Code::Apply(Operand::Symbol(0)),
+                ],
+            ))),
+            Code::Set(Operand::Symbol(4)),
+        ]);
+
+        assert_eq!(
+            Vm::new(input.as_bytes()).validate(),
+            [
+                diagnostic(2, DiagnosticKind::StackOperandOutOfBounds(2)),
+                diagnostic(5, DiagnosticKind::StackOperandOutOfBounds(7)),
+            ]
+        );
+    }
+
+    #[test]
+    fn validate_stack_underflow() {
+        let input = encode_call(vec![Code::Set(Operand::Symbol(4))]);
+
+        assert_eq!(
+            Vm::new(input.as_bytes()).validate(),
+            [diagnostic(1, DiagnosticKind::StackUnderflow)]
+        );
+    }
+
+    #[test]
+    fn validate_shared_code() {
+        // (if #f (begin 0 (rib (get 9))) (begin 0 (rib (get 9))))
+        let branch = vec![
+            Code::Constant(Constant::Number(0)),
+            Code::Get(Operand::Stack(9)),
+            Code::Constant(Constant::Number(1)),
+            Code::Apply(Operand::Symbol(0)),
+        ];
+        let input = encode_program(
+            &[],
+            vec![
+                Code::Get(Operand::Symbol(1)),
+                Code::If(branch.clone(), branch),
+            ],
+        );
+        let mut vm = Vm::new(input.as_bytes());
+
+        // Share the rest of an `else` branch with a `then` branch.
+        let r#if = vm.get_tag(vm.program_counter);
+        let r#else = vm.get_tag(vm.get_tag(r#if));
+        *vm.get_tag_mut(vm.get_cdr(r#if)) = r#else;
+
+        let lines = vm
+            .disassemble()
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.trim() == "get 9")
+            .map(|(index, _)| index + 1)
+            .collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            vm.validate(),
+            lines
+                .into_iter()
+                .map(|line| diagnostic(line, DiagnosticKind::StackOperandOutOfBounds(9)))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn validate_stack_procedures() {
+        // (42) ((rib id 0 1) 42)
+        let input = encode_program(
+            &[],
+            vec![
+                Code::Constant(Constant::Number(42)),
+                Code::Constant(Constant::Number(0)),
+                Code::Apply(Operand::Stack(1)),
+                Code::Constant(Constant::Number(Primitive::Id as u64)),
+                Code::Constant(Constant::Number(0)),
+                Code::Constant(Constant::Number(1)),
+                Code::Constant(Constant::Number(3)),
+                Code::Apply(Operand::Symbol(0)),
+                Code::Constant(Constant::Number(42)),
+                Code::Constant(Constant::Number(1)),
+                Code::Apply(Operand::Stack(2)),
+            ],
+        );
+
+        assert_eq!(
+            Vm::new(input.as_bytes()).validate(),
+            [diagnostic(3, DiagnosticKind::NonProcedureOperand(1))]
+        );
+    }
+
+    #[test]
+    fn validate_illegal_instruction() {
+        let input = encode_call(vec![]);
+        let mut vm = Vm::new(input.as_bytes());
+
+        *vm.get_car_mut(vm.get_tag(vm.program_counter)) = Object::Number(42);
+
+        assert_eq!(
+            vm.validate(),
+            [diagnostic(2, DiagnosticKind::IllegalInstruction(42))]
+        );
+    }
+
+    #[test]
+    fn validate_unterminated_code() {
+        let input = encode_call(vec![]);
+        let mut vm = Vm::new(input.as_bytes());
+
+        *vm.get_tag_mut(vm.program_counter) = ZERO;
+
+        assert_eq!(
+            vm.validate(),
+            [diagnostic(1, DiagnosticKind::UnterminatedCode)]
+        );
+    }
+}